@@ -0,0 +1,32 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use mc_rs::{
+    renderer::{ATLAS_BLOCKS, ATLAS_PATH},
+    texture_atlas::TextureAtlas,
+    world::World,
+    world_generator::WorldGenerator,
+};
+
+fn mesh_chunk(c: &mut Criterion) {
+    let atlas = TextureAtlas::without_texture(ATLAS_PATH.to_owned()).with_blocks(6, &ATLAS_BLOCKS);
+
+    // The surface chunk and everything around it, so faces get culled against the neighbors
+    let generator = WorldGenerator::new(1234);
+    let mut world = World::new(1);
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                world.generate_chunk([x, y, z], &generator);
+            }
+        }
+    }
+    world.compute_light();
+
+    let chunk = world.get_chunk([0, 0, 0]).unwrap();
+    let neighbors = world.neighbors([0, 0, 0]);
+    c.bench_function("mesh surface chunk", |b| {
+        b.iter(|| chunk.generate_mesh(&neighbors, &atlas))
+    });
+}
+
+criterion_group!(benches, mesh_chunk);
+criterion_main!(benches);
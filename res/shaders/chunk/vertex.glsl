@@ -0,0 +1,47 @@
+#version 330
+
+// Packed chunk vertex, see `ChunkVertex`
+in uint data;
+in uint tile;
+
+uniform mat4 transform;
+uniform mat4 view;
+uniform mat4 projection;
+// Textures across and down the atlas
+uniform vec2 atlas_tiles;
+
+out vec3 v_position;
+out vec3 v_normal;
+out vec2 v_uv;
+out vec2 v_light;
+out float v_ao;
+out float v_depth;
+
+const vec3 NORMALS[6] = vec3[6](
+    vec3(1.0, 0.0, 0.0),
+    vec3(-1.0, 0.0, 0.0),
+    vec3(0.0, 1.0, 0.0),
+    vec3(0.0, -1.0, 0.0),
+    vec3(0.0, 0.0, 1.0),
+    vec3(0.0, 0.0, -1.0)
+);
+
+void main() {
+    vec3 position = vec3(data & 31u, (data >> 5) & 31u, (data >> 10) & 31u) - 0.5;
+    vec3 normal = NORMALS[(data >> 15) & 7u];
+    vec2 corner = vec2((data >> 18) & 1u, (data >> 19) & 1u);
+    float ao = float((data >> 20) & 3u) / 3.0;
+    vec2 light = vec2((data >> 22) & 15u, (data >> 26) & 15u) / 15.0;
+
+    uint columns = uint(atlas_tiles.x);
+    vec2 tile_origin = vec2(tile % columns, tile / columns);
+
+    gl_Position = projection * view * transform * vec4(position, 1.0);
+
+    v_position = position;
+    v_normal = transpose(inverse(mat3(transform))) * normal;
+    v_uv = (tile_origin + corner) / atlas_tiles;
+    v_light = light;
+    v_ao = ao;
+    v_depth = gl_Position[3];
+}
@@ -0,0 +1,7 @@
+// Brightness of a block lit with sky and block light between 0 and 1,
+// with the sky light dimmed by `sky_brightness` at night.
+// Every light level is 80% as bright as the one above it.
+float light_brightness(vec2 light, float sky_brightness) {
+    float level = max(light.x * sky_brightness, light.y);
+    return pow(0.8, (1.0 - level) * 15.0);
+}
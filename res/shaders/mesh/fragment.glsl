@@ -0,0 +1,46 @@
+#version 330
+
+#include "../include/light.glsl"
+
+in vec3 v_position;
+in vec3 v_normal;
+in vec2 v_uv;
+in vec2 v_light;
+in float v_ao;
+in float v_depth;
+
+uniform sampler2D albedo;
+uniform vec3 light_direction;
+uniform vec3 light_color;
+uniform float sky_brightness;
+uniform vec3 fog_color;
+uniform float fog_start;
+uniform float fog_end;
+// Pixels with less alpha than this are thrown away
+uniform float alpha_cutoff;
+// Keep the texture alpha for blending, otherwise everything is drawn solid
+uniform bool blend_alpha;
+
+out vec4 final_color;
+
+vec4 color_dark = vec4(0.4, 0.4, 0.45, 1.0);
+
+void main() {
+    final_color = texture(albedo, v_uv);
+    if (final_color.a < alpha_cutoff) {
+        discard;
+    }
+    if (!blend_alpha) {
+        final_color.a = 1.0;
+    }
+    float light_amount = dot(normalize(v_normal), normalize(light_direction));
+    light_amount = clamp(light_amount, 0.0, 1.0);
+
+    final_color = mix(final_color * color_dark, final_color * vec4(light_color, 1.0), light_amount);
+
+    final_color.rgb *= light_brightness(v_light, sky_brightness);
+    final_color.rgb *= mix(0.45, 1.0, v_ao);
+
+    float fog = smoothstep(fog_start, fog_end, v_depth);
+    final_color.rgb = mix(final_color.rgb, fog_color, fog);
+}
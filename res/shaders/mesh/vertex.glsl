@@ -0,0 +1,29 @@
+#version 330
+
+in vec3 position;
+in vec3 normal;
+in vec2 uv;
+in vec2 light;
+in float ao;
+
+uniform mat4 transform;
+uniform mat4 view;
+uniform mat4 projection;
+
+out vec3 v_position;
+out vec3 v_normal;
+out vec2 v_uv;
+out vec2 v_light;
+out float v_ao;
+out float v_depth;
+
+void main() {
+    gl_Position = projection * view * transform * vec4(position, 1.0);
+
+    v_position = position;
+    v_normal = transpose(inverse(mat3(transform))) * normal;
+    v_uv = uv;
+    v_light = light;
+    v_ao = ao;
+    v_depth = gl_Position[3];
+}
@@ -0,0 +1,23 @@
+#version 330
+
+in vec3 v_ray;
+
+uniform vec3 zenith_color;
+uniform vec3 horizon_color;
+uniform vec3 sun_direction;
+uniform vec3 sun_color;
+
+out vec4 final_color;
+
+void main() {
+    vec3 ray = normalize(v_ray);
+
+    float height = clamp(ray.y, 0.0, 1.0);
+    vec3 color = mix(horizon_color, zenith_color, pow(height, 0.6));
+
+    float sun = dot(ray, sun_direction);
+    color = mix(color, sun_color, smoothstep(0.9985, 0.999, sun));
+    color = mix(color, vec3(0.85, 0.88, 0.95), smoothstep(0.9992, 0.9995, -sun));
+
+    final_color = vec4(color, 1.0);
+}
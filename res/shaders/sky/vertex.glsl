@@ -0,0 +1,14 @@
+#version 330
+
+uniform mat4 inverse_view_projection;
+
+out vec3 v_ray;
+
+void main() {
+    // One triangle big enough to cover the whole screen
+    vec2 corner = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2)) * 2.0 - 1.0;
+    gl_Position = vec4(corner, 1.0, 1.0);
+
+    vec4 far_point = inverse_view_projection * vec4(corner, 1.0, 1.0);
+    v_ray = far_point.xyz / far_point.w;
+}
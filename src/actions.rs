@@ -0,0 +1,389 @@
+use std::collections::HashSet;
+
+use glium::glutin::event::{MouseButton, VirtualKeyCode};
+use serde::{Deserialize, Serialize};
+
+use crate::gamepad::Gamepad;
+use crate::keyboard::Keyboard;
+
+pub const BINDINGS_PATH: &str = "bindings.toml";
+
+/// Number of hotbar slots, `Action::Hotbar` goes from 1 up to this.
+pub const HOTBAR_SLOTS: u8 = 9;
+
+/// Something the player wants to do, whatever key or button it came from.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Sneak,
+    Break,
+    Place,
+    Hotbar(u8),
+}
+
+/// Keys and mouse buttons that all have to be held for the action to be active.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub action: Action,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<VirtualKeyCode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buttons: Vec<MouseButton>,
+}
+
+impl Binding {
+    fn keys(action: Action, keys: &[VirtualKeyCode]) -> Binding {
+        Binding {
+            action,
+            keys: keys.to_vec(),
+            buttons: Vec::new(),
+        }
+    }
+
+    fn button(action: Action, button: MouseButton) -> Binding {
+        Binding {
+            action,
+            keys: Vec::new(),
+            buttons: vec![button],
+        }
+    }
+
+    /// Keys and buttons regardless of the order they're listed in.
+    fn chord(&self) -> (HashSet<VirtualKeyCode>, HashSet<MouseButton>) {
+        (
+            self.keys.iter().copied().collect(),
+            self.buttons.iter().copied().collect(),
+        )
+    }
+
+    /// Something tapped since the last frame counts as held too, or it would never be seen.
+    pub fn is_held(&self, input: &Keyboard) -> bool {
+        self.keys
+            .iter()
+            .all(|key| input.is_key_pressed(*key) || input.is_key_just_pressed(*key))
+            && self.buttons.iter().all(|button| {
+                input.is_mouse_button_pressed(*button)
+                    || input.is_mouse_button_just_pressed(*button)
+            })
+    }
+}
+
+/// What every action is bound to, kept in `bindings.toml`. An action can have any number of
+/// bindings, it's active while any one of them is held.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bindings {
+    #[serde(rename = "binding", default)]
+    pub bindings: Vec<Binding>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use VirtualKeyCode::*;
+        let mut bindings = vec![
+            Binding::keys(Action::MoveForward, &[W]),
+            Binding::keys(Action::MoveBackward, &[S]),
+            Binding::keys(Action::MoveLeft, &[A]),
+            Binding::keys(Action::MoveRight, &[D]),
+            Binding::keys(Action::Jump, &[Space]),
+            Binding::keys(Action::Sneak, &[C]),
+            Binding::button(Action::Break, MouseButton::Left),
+            Binding::button(Action::Place, MouseButton::Right),
+        ];
+        let digits = [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+        for (slot, key) in (1..=HOTBAR_SLOTS).zip(digits) {
+            bindings.push(Binding::keys(Action::Hotbar(slot), &[key]));
+        }
+        Bindings { bindings }
+    }
+}
+
+impl Bindings {
+    pub fn load(path: &str) -> Result<Bindings, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read bindings {}: {}", path, e))?;
+        let bindings: Bindings = toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse bindings {}: {}", path, e))?;
+        bindings.validate()?;
+        Ok(bindings)
+    }
+
+    /// Loads the bindings, or writes the defaults out on the first run so there's a file to edit.
+    pub fn load_or_create(path: &str) -> Result<Bindings, String> {
+        if std::path::Path::new(path).exists() {
+            return Bindings::load(path);
+        }
+        let bindings = Bindings::default();
+        bindings.save(path)?;
+        Ok(bindings)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize bindings: {}", e))?;
+        std::fs::write(path, contents)
+            .map_err(|e| format!("Failed to write bindings {}: {}", path, e))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, binding) in self.bindings.iter().enumerate() {
+            if binding.keys.is_empty() && binding.buttons.is_empty() {
+                return Err(format!(
+                    "Binding for {:?} has no keys or buttons",
+                    binding.action
+                ));
+            }
+            let chord = binding.chord();
+            if chord.0.len() < binding.keys.len() || chord.1.len() < binding.buttons.len() {
+                return Err(format!(
+                    "Binding for {:?} lists a key or button twice",
+                    binding.action
+                ));
+            }
+            if let Some(other) = self.bindings[..i]
+                .iter()
+                .find(|other| other.chord() == chord)
+            {
+                return Err(format!(
+                    "{:?} and {:?} are bound to the same keys and buttons",
+                    other.action, binding.action
+                ));
+            }
+            if let Action::Hotbar(slot) = binding.action {
+                if !(1..=HOTBAR_SLOTS).contains(&slot) {
+                    return Err(format!("Hotbar slots go from 1 to {}", HOTBAR_SLOTS));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Actions active this frame, what gameplay code asks instead of looking at keys.
+#[derive(Default)]
+pub struct Actions {
+    active: HashSet<Action>,
+    previous: HashSet<Action>,
+    movement: [f32; 2],
+    look: [f32; 2],
+}
+
+impl Actions {
+    pub fn new() -> Actions {
+        Actions::default()
+    }
+
+    pub fn update(&mut self, bindings: &Bindings, input: &Keyboard, gamepad: &Gamepad) {
+        self.previous = std::mem::take(&mut self.active);
+        for binding in &bindings.bindings {
+            if binding.is_held(input) {
+                self.active.insert(binding.action);
+            }
+        }
+        self.active.extend(gamepad.actions());
+
+        let axis = |negative, positive| {
+            self.is_active(positive) as i32 as f32 - self.is_active(negative) as i32 as f32
+        };
+        let keys = [
+            axis(Action::MoveLeft, Action::MoveRight),
+            axis(Action::MoveBackward, Action::MoveForward),
+        ];
+        let stick = gamepad.left_stick();
+        self.movement = [
+            (keys[0] + stick[0]).clamp(-1.0, 1.0),
+            (keys[1] + stick[1]).clamp(-1.0, 1.0),
+        ];
+        self.look = gamepad.right_stick();
+    }
+
+    pub fn is_active(&self, action: Action) -> bool {
+        self.active.contains(&action)
+    }
+
+    /// Active now but not the last update.
+    pub fn is_just_active(&self, action: Action) -> bool {
+        self.is_active(action) && !self.previous.contains(&action)
+    }
+
+    /// How hard the player wants to move right and forward, from -1 to 1.
+    pub fn movement(&self) -> [f32; 2] {
+        self.movement
+    }
+
+    /// How fast the player wants to look right and up, from -1 to 1.
+    pub fn look(&self) -> [f32; 2] {
+        self.look
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glium::glutin::event::ElementState;
+
+    use super::*;
+
+    fn load(name: &str, contents: &str) -> Result<Bindings, String> {
+        let path = std::env::temp_dir().join(format!(
+            "mc_rs_bindings_{}_{}.toml",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        let bindings = Bindings::load(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        bindings
+    }
+
+    fn update(actions: &mut Actions, bindings: &Bindings, input: &mut Keyboard) {
+        actions.update(bindings, input, &Gamepad::new());
+        input.end_frame();
+    }
+
+    #[test]
+    fn load_reads_keys_buttons_and_hotbar_slots() {
+        let bindings = load(
+            "parse",
+            r#"
+            [[binding]]
+            action = "jump"
+            keys = ["Space"]
+
+            [[binding]]
+            action = { hotbar = 3 }
+            keys = ["LControl", "Key3"]
+
+            [[binding]]
+            action = "break"
+            buttons = ["Left"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            bindings.bindings,
+            [
+                Binding::keys(Action::Jump, &[VirtualKeyCode::Space]),
+                Binding::keys(
+                    Action::Hotbar(3),
+                    &[VirtualKeyCode::LControl, VirtualKeyCode::Key3]
+                ),
+                Binding::button(Action::Break, MouseButton::Left),
+            ]
+        );
+    }
+
+    #[test]
+    fn defaults_survive_a_save_and_load() {
+        let defaults = Bindings::default();
+        let contents = toml::to_string_pretty(&defaults).unwrap();
+        assert_eq!(load("defaults", &contents), Ok(defaults));
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let error = load(
+            "unknown",
+            "[[binding]]\naction = \"jump\"\nkeys = [\"Spacebar\"]\n",
+        )
+        .unwrap_err();
+        assert!(error.starts_with("Failed to parse bindings"), "{}", error);
+        let error = load("action", "[[binding]]\naction = \"fly\"\nkeys = [\"F\"]\n").unwrap_err();
+        assert!(error.starts_with("Failed to parse bindings"), "{}", error);
+    }
+
+    #[test]
+    fn duplicate_keys_are_rejected() {
+        use VirtualKeyCode::*;
+        let twice = Bindings {
+            bindings: vec![
+                Binding::keys(Action::Jump, &[LControl, Space]),
+                Binding::keys(Action::Sneak, &[Space, LControl]),
+            ],
+        };
+        assert_eq!(
+            twice.validate(),
+            Err("Jump and Sneak are bound to the same keys and buttons".to_owned())
+        );
+        let repeated = Bindings {
+            bindings: vec![Binding::keys(Action::Jump, &[Space, Space])],
+        };
+        assert!(repeated.validate().is_err());
+        // A key can still be part of other chords
+        let chords = Bindings {
+            bindings: vec![
+                Binding::keys(Action::Jump, &[Space]),
+                Binding::keys(Action::Sneak, &[LControl, Space]),
+            ],
+        };
+        assert_eq!(chords.validate(), Ok(()));
+    }
+
+    #[test]
+    fn bindings_need_something_to_press_and_a_real_slot() {
+        let empty = Bindings {
+            bindings: vec![Binding::keys(Action::Jump, &[])],
+        };
+        assert!(empty.validate().is_err());
+        for slot in [0, HOTBAR_SLOTS + 1] {
+            let hotbar = Bindings {
+                bindings: vec![Binding::keys(Action::Hotbar(slot), &[VirtualKeyCode::H])],
+            };
+            assert!(hotbar.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn chords_need_everything_held() {
+        let bindings = Bindings {
+            bindings: vec![Binding {
+                action: Action::Break,
+                keys: vec![VirtualKeyCode::LShift],
+                buttons: vec![MouseButton::Left],
+            }],
+        };
+        let mut input = Keyboard::new();
+        let mut actions = Actions::new();
+
+        input.process_mouse_button(MouseButton::Left, ElementState::Pressed);
+        update(&mut actions, &bindings, &mut input);
+        assert!(!actions.is_active(Action::Break));
+
+        input.process_key(VirtualKeyCode::LShift, ElementState::Pressed);
+        update(&mut actions, &bindings, &mut input);
+        assert!(actions.is_active(Action::Break));
+
+        input.process_mouse_button(MouseButton::Left, ElementState::Released);
+        update(&mut actions, &bindings, &mut input);
+        assert!(!actions.is_active(Action::Break));
+    }
+
+    #[test]
+    fn just_active_only_on_the_first_update() {
+        let bindings = Bindings::default();
+        let mut input = Keyboard::new();
+        let mut actions = Actions::new();
+
+        input.process_key(VirtualKeyCode::Space, ElementState::Pressed);
+        update(&mut actions, &bindings, &mut input);
+        assert!(actions.is_just_active(Action::Jump));
+        update(&mut actions, &bindings, &mut input);
+        assert!(actions.is_active(Action::Jump));
+        assert!(!actions.is_just_active(Action::Jump));
+
+        input.process_key(VirtualKeyCode::Space, ElementState::Released);
+        update(&mut actions, &bindings, &mut input);
+        assert!(!actions.is_active(Action::Jump));
+
+        // A tap between two updates still shows up once
+        input.process_key(VirtualKeyCode::Space, ElementState::Pressed);
+        input.process_key(VirtualKeyCode::Space, ElementState::Released);
+        update(&mut actions, &bindings, &mut input);
+        assert!(actions.is_just_active(Action::Jump));
+        update(&mut actions, &bindings, &mut input);
+        assert!(!actions.is_active(Action::Jump));
+    }
+}
@@ -0,0 +1,209 @@
+//! Renders top-down and cross-section maps of the world generator to PNG files,
+//! without opening a window. Default worlds get a map of the hill noise too.
+//!
+//! Usage: genpreview [--seed N] [--world default|flat|debug] [--preset FILE] [--center X Z] [--size N] [--out DIR]
+
+use std::path::PathBuf;
+
+use image::{Rgb, RgbImage};
+use mc_rs::{
+    block::BlockId,
+    generator_settings::GeneratorSettings,
+    renderer::ATLAS_BLOCKS,
+    world::{WorldMetadata, WorldType},
+    world_generator::{TerrainGenerator, WorldGenerator},
+};
+
+struct Options {
+    metadata: WorldMetadata,
+    center: [i64; 2],
+    size: i64,
+    out: PathBuf,
+}
+
+impl Options {
+    fn from_args() -> Options {
+        let mut options = Options {
+            metadata: WorldMetadata::new(0, WorldType::Default),
+            center: [0, 0],
+            size: 256,
+            out: PathBuf::from("preview"),
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().unwrap_or_else(|| usage(&arg));
+            match arg.as_str() {
+                "--seed" => {
+                    options.metadata.seed = value().parse().unwrap_or_else(|_| usage("--seed"));
+                }
+                "--world" => {
+                    options.metadata.world_type =
+                        value().parse().unwrap_or_else(|e: String| usage(&e));
+                }
+                "--preset" => {
+                    options.metadata.generator_settings =
+                        GeneratorSettings::load(&value()).unwrap_or_else(|e| usage(&e));
+                }
+                "--center" => {
+                    options.center[0] = value().parse().unwrap_or_else(|_| usage("--center"));
+                    options.center[1] = value().parse().unwrap_or_else(|_| usage("--center"));
+                }
+                "--size" => options.size = value().parse().unwrap_or_else(|_| usage("--size")),
+                "--out" => options.out = PathBuf::from(value()),
+                _ => usage(&arg),
+            }
+        }
+
+        if options.size <= 0 {
+            usage("--size");
+        }
+        options.metadata.validate().unwrap_or_else(|e| usage(&e));
+        options
+    }
+}
+
+fn usage(problem: &str) -> ! {
+    eprintln!("genpreview: bad argument {}", problem);
+    eprintln!(
+        "Usage: genpreview [--seed N] [--world default|flat|debug] [--preset FILE] [--center X Z] [--size N] [--out DIR]"
+    );
+    std::process::exit(1);
+}
+
+fn block_color(block: BlockId) -> Rgb<u8> {
+    match block {
+        BlockId::Air => Rgb([204, 217, 255]),
+        BlockId::Dirt => Rgb([121, 85, 58]),
+        BlockId::Grass => Rgb([77, 148, 85]),
+        BlockId::Stone => Rgb([161, 170, 167]),
+        BlockId::Sand => Rgb([219, 207, 163]),
+        BlockId::Water => Rgb([64, 96, 200]),
+        BlockId::Log => Rgb([92, 64, 36]),
+        BlockId::Leaves => Rgb([40, 100, 35]),
+        BlockId::Lamp => Rgb([250, 215, 120]),
+        BlockId::Glass => Rgb([200, 225, 235]),
+    }
+}
+
+/// The blocks of a region, terrain and features both, generated lazily column by column.
+struct Region<'a> {
+    generator: &'a dyn TerrainGenerator,
+    min: [i64; 3],
+    height: i64,
+    features: std::collections::HashMap<[i64; 3], BlockId>,
+}
+
+impl<'a> Region<'a> {
+    fn new(
+        generator: &'a dyn TerrainGenerator,
+        center: [i64; 2],
+        size: i64,
+        y_range: std::ops::Range<i64>,
+    ) -> Region<'a> {
+        let min = [center[0] - size / 2, y_range.start, center[1] - size / 2];
+        let height = y_range.end - y_range.start;
+        let mut features = std::collections::HashMap::new();
+        for (point, block) in generator.features_in(min, [size, height, size]) {
+            // Same rule the chunks follow when they apply features
+            if block == BlockId::Leaves
+                && features
+                    .get(&point)
+                    .copied()
+                    .unwrap_or(generator.get_block_at(point))
+                    != BlockId::Air
+            {
+                continue;
+            }
+            features.insert(point, block);
+        }
+
+        Region {
+            generator,
+            min,
+            height,
+            features,
+        }
+    }
+
+    fn get_block_at(&self, point: [i64; 3]) -> BlockId {
+        self.features
+            .get(&point)
+            .copied()
+            .unwrap_or_else(|| self.generator.get_block_at(point))
+    }
+
+    /// Topmost non-air block of a column, if any.
+    fn top(&self, x: i64, z: i64) -> Option<(i64, BlockId)> {
+        (self.min[1]..self.min[1] + self.height)
+            .rev()
+            .map(|y| (y, self.get_block_at([x, y, z])))
+            .find(|(_, block)| *block != BlockId::Air)
+    }
+}
+
+fn main() {
+    let options = Options::from_args();
+    std::fs::create_dir_all(&options.out).expect("Failed to create output directory");
+
+    // Same blocks as the game, the debug world lays them out in this order
+    let generator = options.metadata.create_generator(&ATLAS_BLOCKS);
+    let settings = &options.metadata.generator_settings;
+    let region = Region::new(
+        generator.as_ref(),
+        options.center,
+        options.size,
+        settings.min_y..settings.max_y,
+    );
+    let size = options.size as u32;
+
+    let mut heightmap = RgbImage::new(size, size);
+    let mut surface = RgbImage::new(size, size);
+    for i in 0..size {
+        for j in 0..size {
+            let x = region.min[0] + i as i64;
+            let z = region.min[2] + j as i64;
+
+            let (y, block) = region.top(x, z).unwrap_or((region.min[1], BlockId::Air));
+            let shade = ((y - region.min[1]) * 255 / (region.height - 1)) as u8;
+            heightmap.put_pixel(i, j, Rgb([shade, shade, shade]));
+            surface.put_pixel(i, j, block_color(block));
+        }
+    }
+
+    // Vertical slice along x through the center, sky at the top
+    let height = region.height as u32;
+    let mut section = RgbImage::new(size, height);
+    for i in 0..size {
+        for j in 0..height {
+            let y = region.min[1] + j as i64;
+            let block = region.get_block_at([region.min[0] + i as i64, y, options.center[1]]);
+            section.put_pixel(i, height - 1 - j, block_color(block));
+        }
+    }
+
+    let mut images = vec![
+        ("heightmap.png", heightmap),
+        ("surface.png", surface),
+        ("section.png", section),
+    ];
+    if options.metadata.world_type == WorldType::Default {
+        // Bright where the hills are high, dark where the land stays flat
+        let noise = WorldGenerator::with_settings(options.metadata.seed, settings.clone());
+        let hills = RgbImage::from_fn(size, size, |i, j| {
+            let x = region.min[0] + i as i64;
+            let z = region.min[2] + j as i64;
+            let shade = (noise.hill_noise(x, z) * 255.0) as u8;
+            Rgb([shade, shade, shade])
+        });
+        images.push(("hills.png", hills));
+    }
+
+    for (name, image) in &images {
+        let path = options.out.join(name);
+        image
+            .save(&path)
+            .unwrap_or_else(|e| panic!("Failed to write {}: {}", path.display(), e));
+        println!("Wrote {}", path.display());
+    }
+}
@@ -1,4 +1,7 @@
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum BlockId {
     Air,
     Dirt,
@@ -6,4 +9,73 @@ pub enum BlockId {
     Stone,
     Sand,
     Water,
+    Log,
+    Leaves,
+    Lamp,
+    Glass,
+}
+
+/// Which pass a block is drawn in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RenderLayer {
+    /// Solid blocks, drawn first.
+    Opaque,
+    /// Blocks with fully see-through holes in their texture, like leaves.
+    Cutout,
+    /// Blocks you can see through, blended over everything else back to front.
+    Translucent,
+}
+
+impl RenderLayer {
+    pub const ALL: [RenderLayer; 3] = [
+        RenderLayer::Opaque,
+        RenderLayer::Cutout,
+        RenderLayer::Translucent,
+    ];
+}
+
+impl BlockId {
+    pub const ALL: [BlockId; 10] = [
+        BlockId::Air,
+        BlockId::Dirt,
+        BlockId::Grass,
+        BlockId::Stone,
+        BlockId::Sand,
+        BlockId::Water,
+        BlockId::Log,
+        BlockId::Leaves,
+        BlockId::Lamp,
+        BlockId::Glass,
+    ];
+
+    /// Whether the block stops light from going through it.
+    pub fn is_opaque(&self) -> bool {
+        !matches!(
+            self,
+            BlockId::Air | BlockId::Water | BlockId::Leaves | BlockId::Glass
+        )
+    }
+
+    pub fn render_layer(&self) -> RenderLayer {
+        match self {
+            BlockId::Leaves => RenderLayer::Cutout,
+            BlockId::Water | BlockId::Glass => RenderLayer::Translucent,
+            _ => RenderLayer::Opaque,
+        }
+    }
+
+    /// Whether a face of this block is hidden by `neighbor` sitting right in front of it.
+    /// Next to each other, water and glass blocks merge into one volume.
+    pub fn face_hidden_by(&self, neighbor: BlockId) -> bool {
+        neighbor.is_opaque()
+            || (neighbor == *self && self.render_layer() == RenderLayer::Translucent)
+    }
+
+    /// Block light level the block gives off by itself.
+    pub fn light_emission(&self) -> u8 {
+        match self {
+            BlockId::Lamp => 15,
+            _ => 0,
+        }
+    }
 }
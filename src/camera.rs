@@ -1,4 +1,4 @@
-use crate::{transform::Transform, vector3::Vector3};
+use crate::{player::MAX_PITCH, transform::Transform, vector3::Vector3, world::World};
 use nalgebra_glm as glm;
 
 pub trait Camera {
@@ -33,6 +33,14 @@ impl PerspectiveCamera3D {
             clip_far: far,
         }
     }
+
+    /// Matches the aspect ratio to a viewport of `width` by `height` pixels.
+    /// A minimized window has no size, the old aspect is kept then.
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
 }
 
 impl Camera for PerspectiveCamera3D {
@@ -40,12 +48,331 @@ impl Camera for PerspectiveCamera3D {
         glm::perspective(self.aspect, self.fov, self.clip_near, self.clip_far)
     }
     fn view(&self, transform: Transform) -> glm::Mat4 {
-        let mut lookat_point = glm::Vec3::zeros();
-        lookat_point.x = transform.get_rotation().y.cos() * transform.get_rotation().x.cos();
-        lookat_point.y = transform.get_rotation().x.sin();
-        lookat_point.z = transform.get_rotation().y.sin() * transform.get_rotation().x.cos();
+        look_along(transform)
+    }
+}
+
+/// Looks from the position of `transform` the way it faces.
+fn look_along(transform: Transform) -> glm::Mat4 {
+    let position: glm::Vec3 = transform.position.into();
+    let forward: glm::Vec3 = transform.forward().into();
+    glm::look_at_rh(&position, &(position + forward), &transform.up().into())
+}
+
+/// No perspective, things are the same size however far away they are. `height` is how much of
+/// the world fits from the bottom of the screen to the top.
+pub struct OrthographicCamera {
+    pub aspect: f32,
+    pub height: f32,
+    pub clip_near: f32,
+    pub clip_far: f32,
+}
+
+impl OrthographicCamera {
+    pub fn new(aspect: f32, height: f32, near: f32, far: f32) -> OrthographicCamera {
+        OrthographicCamera {
+            aspect,
+            height,
+            clip_near: near,
+            clip_far: far,
+        }
+    }
+
+    /// Same as `PerspectiveCamera3D::set_viewport`.
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn projection(&self) -> glm::Mat4 {
+        let half_height = self.height / 2.0;
+        let half_width = half_height * self.aspect;
+        glm::ortho(
+            -half_width,
+            half_width,
+            -half_height,
+            half_height,
+            self.clip_near,
+            self.clip_far,
+        )
+    }
+    fn view(&self, transform: Transform) -> glm::Mat4 {
+        look_along(transform)
+    }
+}
+
+/// Whose eyes the world is seen through.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CameraMode {
+    #[default]
+    FirstPerson,
+    ThirdPerson,
+    /// Top down with `MapCamera`.
+    Map,
+}
+
+impl CameraMode {
+    pub fn next(self) -> CameraMode {
+        match self {
+            CameraMode::FirstPerson => CameraMode::ThirdPerson,
+            CameraMode::ThirdPerson => CameraMode::Map,
+            CameraMode::Map => CameraMode::FirstPerson,
+        }
+    }
+}
+
+/// Follows a little way behind whatever it's looking at, moving in closer when blocks are in
+/// the way. It can swing around the target too, see `orbit`.
+pub struct ThirdPersonCamera {
+    pub distance: f32,
+    /// Room kept between the near plane and a block, so it doesn't cut into it.
+    pub margin: f32,
+    /// Radians the camera is swung left around the target and tilted up past it.
+    orbit: [f32; 2],
+}
+
+impl Default for ThirdPersonCamera {
+    fn default() -> Self {
+        ThirdPersonCamera::new(4.0, 0.2)
+    }
+}
+
+impl ThirdPersonCamera {
+    pub fn new(distance: f32, margin: f32) -> ThirdPersonCamera {
+        ThirdPersonCamera {
+            distance,
+            margin,
+            orbit: [0.0, 0.0],
+        }
+    }
+
+    /// Swings the camera left by `yaw` and up by `pitch` radians around the target. The tilt
+    /// stops short of straight up and down, same as the player's.
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        self.orbit[0] = (self.orbit[0] + yaw) % std::f32::consts::TAU;
+        self.orbit[1] = (self.orbit[1] + pitch).clamp(-2.0 * MAX_PITCH, 2.0 * MAX_PITCH);
+    }
+
+    /// Back behind the target.
+    pub fn reset_orbit(&mut self) {
+        self.orbit = [0.0, 0.0];
+    }
+
+    /// Where to look from to see `target` through `camera`, from behind and facing the same way
+    /// unless swung around with `orbit`. The whole near plane is kept out of blocks, not just
+    /// its middle, by casting a ray from each of its corners.
+    pub fn place(
+        &self,
+        target: Transform,
+        camera: &PerspectiveCamera3D,
+        world: &World,
+    ) -> Transform {
+        let pitch = target.forward().y.clamp(-1.0, 1.0).asin();
+        let orbit_pitch = (pitch + self.orbit[1]).clamp(-MAX_PITCH, MAX_PITCH) - pitch;
+        let mut transform = target;
+        transform.rotation = glm::quat_angle_axis(self.orbit[0], &glm::Vec3::y())
+            * target.rotation
+            * glm::quat_angle_axis(orbit_pitch, &glm::Vec3::x());
+
+        let back = transform.forward() * -1.0;
+        let half_height = camera.clip_near * (camera.fov / 2.0).tan();
+        let half_width = half_height * camera.aspect;
+        let corners = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]].map(|[x, y]| {
+            transform.right() * (x * half_width) + transform.up() * (y * half_height)
+        });
+        // The eye has to stay out of blocks, the near plane in front of it too
+        let eye = self.free_distance(world, target.position, back);
+        let near_plane = corners
+            .iter()
+            .map(|corner| {
+                self.free_distance(world, target.position + *corner, back) + camera.clip_near
+            })
+            .fold(eye, f32::min);
+
+        transform.position = target.position + back * near_plane.min(self.distance);
+        transform
+    }
+
+    /// How far along `direction` from `origin` there's room, less the margin.
+    fn free_distance(&self, world: &World, origin: Vector3, direction: Vector3) -> f32 {
+        match world.raycast(origin.into(), direction.into(), self.distance) {
+            Some(hit) => (hit.distance - self.margin).max(0.0),
+            None => self.distance,
+        }
+    }
+}
+
+/// Looks straight down on a target from high above, with the top of the screen facing the way
+/// the target does. Fog goes by distance from a perspective eye, so it never covers the map.
+pub struct MapCamera {
+    pub camera: OrthographicCamera,
+    /// How high above the target the camera sits.
+    pub altitude: f32,
+}
+
+impl Default for MapCamera {
+    fn default() -> Self {
+        MapCamera {
+            camera: OrthographicCamera::new(16.0 / 9.0, 48.0, 0.1, 256.0),
+            altitude: 64.0,
+        }
+    }
+}
+
+impl MapCamera {
+    pub fn place(&self, target: Transform) -> Transform {
+        let forward = target.forward();
+        let yaw = (-forward.x).atan2(-forward.z);
+        let mut transform = target;
+        transform.position.y += self.altitude;
+        transform.set_yaw_pitch(yaw, -std::f32::consts::FRAC_PI_2);
+        transform
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::BlockId,
+        flat_generator::{FlatGenerator, FlatLayer},
+        vector3::Vector3,
+    };
+
+    #[test]
+    fn orthographic_fits_height_and_aspect() {
+        let camera = OrthographicCamera::new(2.0, 10.0, 0.1, 100.0);
+        let corner = camera.projection() * glm::vec4(10.0, 5.0, -50.0, 1.0);
+        assert!((corner.x - 1.0).abs() < 1e-5);
+        assert!((corner.y - 1.0).abs() < 1e-5);
+
+        // Farther away is no smaller
+        let far = camera.projection() * glm::vec4(10.0, 5.0, -90.0, 1.0);
+        assert!((far.x - corner.x).abs() < 1e-5);
+    }
+
+    // Stone floor with its top at y = -1, looking along -z from above it
+    fn world_and_target() -> (World, Transform) {
+        let generator = FlatGenerator::new(&[FlatLayer::new(BlockId::Stone, 16)]);
+        let mut world = World::new(1);
+        for y in -1..1 {
+            world.generate_chunk([0, y, 0], &generator);
+        }
+        let mut target = Transform::zero();
+        target.position = Vector3 {
+            x: 8.0,
+            y: 2.0,
+            z: 4.0,
+        };
+        (world, target)
+    }
+
+    // Square with a near plane small enough to hide behind the margin
+    fn camera() -> PerspectiveCamera3D {
+        PerspectiveCamera3D::new(1.0, 90f32.to_radians(), 0.01, 100.0)
+    }
+
+    fn assert_near(actual: Vector3, expected: [f32; 3]) {
+        let actual: [f32; 3] = actual.into();
+        assert!(
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(a, e)| (a - e).abs() < 1e-4),
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn third_person_stays_behind_in_the_open() {
+        let (world, target) = world_and_target();
+        let placed = ThirdPersonCamera::new(4.0, 0.2).place(target, &camera(), &world);
+        assert_near(placed.position, [8.0, 2.0, 8.0]);
+    }
+
+    #[test]
+    fn third_person_moves_in_front_of_blocks() {
+        let (mut world, target) = world_and_target();
+        world.set_block([8, 2, 6], BlockId::Stone);
+        let placed = ThirdPersonCamera::new(4.0, 0.2).place(target, &camera(), &world);
+        // The block's face is 1.5 behind, the margin comes off that
+        assert!((placed.position.z - 5.3).abs() < 1e-5);
+    }
+
+    #[test]
+    fn third_person_stays_out_of_walls() {
+        let (mut world, mut target) = world_and_target();
+        // Off the middle of a block, looking down -x with a wall of stone behind at x = 6
+        target.position = Vector3 {
+            x: 4.3,
+            y: 2.2,
+            z: 4.1,
+        };
+        target.set_yaw_pitch(90f32.to_radians(), 0.0);
+        for y in 0..4 {
+            for z in 0..16 {
+                world.set_block([6, y, z], BlockId::Stone);
+            }
+        }
+        let third_person = ThirdPersonCamera::new(4.0, 0.2);
+        let placed = third_person.place(target, &camera(), &world);
+        let face = 6.0 - 0.5;
+        assert!(placed.position.x > target.position.x);
+        assert!(placed.position.x <= face - third_person.margin + 1e-5);
+    }
+
+    #[test]
+    fn near_plane_corners_stay_out_of_blocks() {
+        let (mut world, target) = world_and_target();
+        // Up and to the right of the line behind the target, where only a corner runs into it
+        world.set_block([9, 3, 6], BlockId::Stone);
+        let camera = PerspectiveCamera3D::new(1.0, 90f32.to_radians(), 0.8, 100.0);
+        let placed = ThirdPersonCamera::new(4.0, 0.2).place(target, &camera, &world);
+        // The near plane stops the margin short of the block's face at z = 5.5
+        assert_near(placed.position, [8.0, 2.0, 5.5 - 0.2 + 0.8]);
+    }
+
+    #[test]
+    fn orbit_swings_around_the_target() {
+        let (world, target) = world_and_target();
+        let mut third_person = ThirdPersonCamera::new(4.0, 0.2);
+        third_person.orbit(90f32.to_radians(), 0.0);
+        let placed = third_person.place(target, &camera(), &world);
+        // Swung left, so it's off to the right looking back at the target
+        assert_near(placed.position, [12.0, 2.0, 4.0]);
+        assert_near(placed.forward(), [-1.0, 0.0, 0.0]);
+
+        third_person.reset_orbit();
+        third_person.orbit(0.0, -45f32.to_radians());
+        let placed = third_person.place(target, &camera(), &world);
+        let height = 4.0 * 45f32.to_radians().sin();
+        assert_near(placed.position, [8.0, 2.0 + height, 4.0 + height]);
+    }
+
+    #[test]
+    fn orbit_stops_short_of_straight_down() {
+        let (world, target) = world_and_target();
+        let mut third_person = ThirdPersonCamera::new(4.0, 0.2);
+        third_person.orbit(0.0, -10.0);
+        let placed = third_person.place(target, &camera(), &world);
+        assert!(placed.forward().y > -1.0);
+        assert!((placed.forward().y - (-MAX_PITCH).sin()).abs() < 1e-5);
+    }
 
-        lookat_point += &transform.position.into();
-        glm::look_at_rh(&transform.position.into(), &lookat_point, &glm::Vec3::y())
+    #[test]
+    fn map_looks_down_with_the_target_facing_up_the_screen() {
+        let mut target = Transform::zero();
+        target.set_yaw_pitch(90f32.to_radians(), 30f32.to_radians());
+        let map = MapCamera::default();
+        let placed = map.place(target);
+        assert_near(placed.position, [0.0, map.altitude, 0.0]);
+        assert_near(placed.forward(), [0.0, -1.0, 0.0]);
+        // Facing -x, that's up on the screen
+        assert_near(placed.up(), [-1.0, 0.0, 0.0]);
     }
 }
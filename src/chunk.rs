@@ -1,27 +1,171 @@
+use glium::backend::Facade;
+
 use crate::{
-    block::BlockId,
+    block::{BlockId, RenderLayer},
+    lighting::{LightChannel, MAX_LIGHT},
     mesh::Mesh,
     texture_atlas::{BlockUv, TextureAtlas},
     transform::Transform,
-    vector3::Vector3,
-    vertex::Vertex,
-    world_generator::WorldGenerator,
+    vertex::{ChunkVertex, Vertex},
+    visibility::ChunkVisibility,
+    world_generator::TerrainGenerator,
 };
 
-const CHUNK_HEIGHT: usize = 256;
-const CHUNK_WIDTH: usize = 16;
-const CHUNK_DEPTH: usize = 16;
+pub const CHUNK_HEIGHT: usize = 16;
+pub const CHUNK_WIDTH: usize = 16;
+pub const CHUNK_DEPTH: usize = 16;
 
 pub enum ChunkNeighbor {
     LeftNeighbor,
     RightNeighbor,
     FrontNeighbor,
     BackNeighbor,
+    TopNeighbor,
+    BottomNeighbor,
+}
+
+/// A chunk and the 26 chunks around it, `None` where there is nothing loaded.
+/// Indexed by chunk offset + 1, in the same `h`, `w`, `d` order as the block data,
+/// so `chunks[1][1][1]` is the chunk in the middle.
+#[derive(Clone, Copy, Default)]
+pub struct ChunkNeighbors<'a> {
+    pub chunks: [[[Option<&'a Chunk>; 3]; 3]; 3],
+}
+
+impl<'a> ChunkNeighbors<'a> {
+    pub fn get(&self, neighbor: ChunkNeighbor) -> Option<&'a Chunk> {
+        match neighbor {
+            ChunkNeighbor::LeftNeighbor => self.chunks[1][0][1],
+            ChunkNeighbor::RightNeighbor => self.chunks[1][2][1],
+            ChunkNeighbor::FrontNeighbor => self.chunks[1][1][2],
+            ChunkNeighbor::BackNeighbor => self.chunks[1][1][0],
+            ChunkNeighbor::TopNeighbor => self.chunks[2][1][1],
+            ChunkNeighbor::BottomNeighbor => self.chunks[0][1][1],
+        }
+    }
+
+    /// The chunk holding the block at `h`, `w`, `d` relative to the middle chunk,
+    /// and the index of the block inside of it.
+    /// The coordinates may be up to one chunk past the middle one on every axis.
+    pub fn block_at(&self, h: isize, w: isize, d: isize) -> Option<(&'a Chunk, [usize; 3])> {
+        let (ch, h) = (
+            h.div_euclid(CHUNK_HEIGHT as isize),
+            h.rem_euclid(CHUNK_HEIGHT as isize),
+        );
+        let (cw, w) = (
+            w.div_euclid(CHUNK_WIDTH as isize),
+            w.rem_euclid(CHUNK_WIDTH as isize),
+        );
+        let (cd, d) = (
+            d.div_euclid(CHUNK_DEPTH as isize),
+            d.rem_euclid(CHUNK_DEPTH as isize),
+        );
+        self.chunks[(ch + 1) as usize][(cw + 1) as usize][(cd + 1) as usize]
+            .map(|chunk| (chunk, [h as usize, w as usize, d as usize]))
+    }
+
+    /// Whether the block at `h`, `w`, `d` relative to the middle chunk blocks light.
+    /// Missing chunks don't.
+    pub fn is_opaque_at(&self, h: isize, w: isize, d: isize) -> bool {
+        self.block_at(h, w, d)
+            .is_some_and(|(chunk, [h, w, d])| chunk.data[h][w][d].is_opaque())
+    }
+}
+
+/// How much a vertex is left uncovered by the blocks touching its corner, in front of its face,
+/// from 0 (fully occluded) to 3 (open).
+/// `side1` and `side2` are the blocks next to the corner along the face, `corner` the one diagonal to it.
+pub fn vertex_ao(side1: bool, side2: bool, corner: bool) -> u8 {
+    if side1 && side2 {
+        0
+    } else {
+        3 - (side1 as u8 + side2 as u8 + corner as u8)
+    }
 }
 
+/// Quads are split along the diagonal between vertex 0 and 2. When the other diagonal joins
+/// the brighter corners it has to be used instead, otherwise the same occlusion looks
+/// different depending on how the face is rotated.
+pub fn should_flip_quad(ao: [u8; 4]) -> bool {
+    ao[1] + ao[3] > ao[0] + ao[2]
+}
+
+/// The faces of a chunk, split up by the pass they are drawn in.
+pub struct ChunkMesh {
+    pub opaque: Mesh<ChunkVertex>,
+    pub cutout: Mesh<ChunkVertex>,
+    pub translucent: Mesh<ChunkVertex>,
+    /// How the faces of the chunk connect, for cave culling.
+    pub visibility: ChunkVisibility,
+}
+
+impl ChunkMesh {
+    pub fn empty() -> ChunkMesh {
+        let mut chunk_mesh = ChunkMesh {
+            opaque: Mesh::empty(),
+            cutout: Mesh::empty(),
+            translucent: Mesh::empty(),
+            visibility: ChunkVisibility::all(),
+        };
+        for layer in RenderLayer::ALL {
+            chunk_mesh.layer_mut(layer).layer = layer;
+        }
+        chunk_mesh
+    }
+
+    pub fn layer(&self, layer: RenderLayer) -> &Mesh<ChunkVertex> {
+        match layer {
+            RenderLayer::Opaque => &self.opaque,
+            RenderLayer::Cutout => &self.cutout,
+            RenderLayer::Translucent => &self.translucent,
+        }
+    }
+
+    pub fn layer_mut(&mut self, layer: RenderLayer) -> &mut Mesh<ChunkVertex> {
+        match layer {
+            RenderLayer::Opaque => &mut self.opaque,
+            RenderLayer::Cutout => &mut self.cutout,
+            RenderLayer::Translucent => &mut self.translucent,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        RenderLayer::ALL
+            .iter()
+            .all(|layer| self.layer(*layer).indices.is_empty())
+    }
+
+    pub fn build(&mut self, facade: &dyn Facade) {
+        for layer in RenderLayer::ALL {
+            let mesh = self.layer_mut(layer);
+            if !mesh.indices.is_empty() || mesh.is_built() {
+                mesh.build(facade);
+            }
+        }
+    }
+
+    /// Swaps in the faces of a fresh mesh of the same chunk, after it changed,
+    /// writing them into the GPU buffers this one already has.
+    pub fn update(&mut self, facade: &dyn Facade, mut chunk_mesh: ChunkMesh) {
+        for layer in RenderLayer::ALL {
+            let new = chunk_mesh.layer_mut(layer);
+            let mesh = self.layer_mut(layer);
+            mesh.transform = new.transform;
+            mesh.vertices = std::mem::take(&mut new.vertices);
+            mesh.indices = std::mem::take(&mut new.indices);
+        }
+        self.visibility = chunk_mesh.visibility;
+        self.build(facade);
+    }
+}
+
+/// A cube of blocks. `transform.position` is in chunk units on all three axes,
+/// so chunk (0, -1, 0) holds the blocks right below y = 0.
 pub struct Chunk {
     pub transform: Transform,
     pub data: [[[BlockId; CHUNK_DEPTH]; CHUNK_WIDTH]; CHUNK_HEIGHT],
+    /// Sky light in the high nibble, block light in the low one.
+    light: [[[u8; CHUNK_DEPTH]; CHUNK_WIDTH]; CHUNK_HEIGHT],
 }
 
 impl Chunk {
@@ -29,34 +173,183 @@ impl Chunk {
         Chunk {
             transform: *transform,
             data: [[[BlockId::Air; CHUNK_DEPTH]; CHUNK_WIDTH]; CHUNK_HEIGHT],
+            light: [[[0; CHUNK_DEPTH]; CHUNK_WIDTH]; CHUNK_HEIGHT],
         }
     }
 
-    pub fn generate_data(&mut self, generator: &WorldGenerator) {
-        let factor = 3;
+    pub fn get_light(&self, channel: LightChannel, h: usize, w: usize, d: usize) -> u8 {
+        match channel {
+            LightChannel::Sky => self.light[h][w][d] >> 4,
+            LightChannel::Block => self.light[h][w][d] & 0x0F,
+        }
+    }
+
+    pub fn set_light(&mut self, channel: LightChannel, h: usize, w: usize, d: usize, level: u8) {
+        let light = &mut self.light[h][w][d];
+        *light = match channel {
+            LightChannel::Sky => (*light & 0x0F) | (level.min(MAX_LIGHT) << 4),
+            LightChannel::Block => (*light & 0xF0) | level.min(MAX_LIGHT),
+        };
+    }
+
+    pub fn clear_light(&mut self) {
+        self.light = [[[0; CHUNK_DEPTH]; CHUNK_WIDTH]; CHUNK_HEIGHT];
+    }
+
+    /// Chunk coordinates of this chunk.
+    pub fn position(&self) -> [i64; 3] {
+        [
+            self.transform.position.x as i64,
+            self.transform.position.y as i64,
+            self.transform.position.z as i64,
+        ]
+    }
+
+    /// World coordinates of the block at `data[0][0][0]`.
+    pub fn origin(&self) -> [i64; 3] {
+        let position = self.position();
+        [
+            position[0] * CHUNK_WIDTH as i64,
+            position[1] * CHUNK_HEIGHT as i64,
+            position[2] * CHUNK_DEPTH as i64,
+        ]
+    }
+
+    pub fn generate_data(&mut self, generator: &dyn TerrainGenerator) {
+        let origin = self.origin();
         for h in 0..CHUNK_HEIGHT {
             for w in 0..CHUNK_WIDTH {
                 for d in 0..CHUNK_DEPTH {
                     self.data[h][w][d] = generator.get_block_at([
-                        w as i64 + self.transform.position.x as i64 * CHUNK_WIDTH as i64,
-                        h as i64,
-                        d as i64 + self.transform.position.z as i64 * CHUNK_DEPTH as i64,
+                        w as i64 + origin[0],
+                        h as i64 + origin[1],
+                        d as i64 + origin[2],
                     ]);
                 }
             }
         }
+
+        for (point, block) in generator.features_in(
+            origin,
+            [CHUNK_WIDTH as i64, CHUNK_HEIGHT as i64, CHUNK_DEPTH as i64],
+        ) {
+            let h = (point[1] - origin[1]) as usize;
+            let w = (point[0] - origin[0]) as usize;
+            let d = (point[2] - origin[2]) as usize;
+            // Leaves only grow into the air, everything else carves its way in
+            if block == BlockId::Leaves && self.data[h][w][d] != BlockId::Air {
+                continue;
+            }
+            self.data[h][w][d] = block;
+        }
+    }
+
+    /// Light reaching the face of `block` looking at the block at `h`, `w`, `d`, as sky and
+    /// block light between 0 and 1, or `None` if the block there hides the face.
+    /// Missing neighbors count as open sky so the border stays closed.
+    fn face_light_at(
+        neighbors: &ChunkNeighbors,
+        block: BlockId,
+        h: isize,
+        w: isize,
+        d: isize,
+    ) -> Option<[f32; 2]> {
+        let Some((chunk, [h, w, d])) = neighbors.block_at(h, w, d) else {
+            return Some([1.0, 0.0]);
+        };
+        if block.face_hidden_by(chunk.data[h][w][d]) {
+            return None;
+        }
+        Some([
+            chunk.get_light(LightChannel::Sky, h, w, d) as f32 / MAX_LIGHT as f32,
+            chunk.get_light(LightChannel::Block, h, w, d) as f32 / MAX_LIGHT as f32,
+        ])
+    }
+
+    /// Ambient occlusion of each vertex of a face of the block at `h`, `w`, `d`,
+    /// looking at the blocks in front of the face around every corner.
+    pub fn face_ao(neighbors: &ChunkNeighbors, block: [usize; 3], quad: &[Vertex; 4]) -> [u8; 4] {
+        let center = [block[1] as f32, block[0] as f32, block[2] as f32];
+        quad.map(|vertex| {
+            // x, y, z offsets of the block in front of the face, and of the corner
+            let normal = vertex.normal.map(|n| n as isize);
+            let corner: [isize; 3] = std::array::from_fn(|i| {
+                if normal[i] != 0 {
+                    0
+                } else if vertex.position[i] > center[i] {
+                    1
+                } else {
+                    -1
+                }
+            });
+            let tangents: Vec<usize> = (0..3).filter(|i| normal[*i] == 0).collect();
+
+            let is_opaque = |offset: [isize; 3]| {
+                neighbors.is_opaque_at(
+                    block[0] as isize + normal[1] + offset[1],
+                    block[1] as isize + normal[0] + offset[0],
+                    block[2] as isize + normal[2] + offset[2],
+                )
+            };
+            let along = |axis: usize| {
+                let mut offset = [0; 3];
+                offset[axis] = corner[axis];
+                offset
+            };
+            vertex_ao(
+                is_opaque(along(tangents[0])),
+                is_opaque(along(tangents[1])),
+                is_opaque(corner),
+            )
+        })
+    }
+
+    fn add_face(
+        mesh: &mut Mesh<ChunkVertex>,
+        neighbors: &ChunkNeighbors,
+        block: [usize; 3],
+        quad: [Vertex; 4],
+        light: [f32; 2],
+        tiles: [u32; 2],
+    ) {
+        let ao = Chunk::face_ao(neighbors, block, &quad);
+        let mut quad = quad;
+        for (vertex, ao) in quad.iter_mut().zip(ao) {
+            *vertex = vertex
+                .with_light(light[0], light[1])
+                .with_ao(ao as f32 / 3.0);
+        }
+
+        let quad = ChunkVertex::pack_quad(&quad, tiles);
+        if should_flip_quad(ao) {
+            mesh.add_quad_flipped(quad);
+        } else {
+            mesh.add_quad(quad);
+        }
     }
 
-    // TODO! Avoid generating faces that are hidden by the neighbors.
-    pub fn generate_mesh(
-        &self,
-        neighbor_left: Option<&Chunk>,
-        neighbor_right: Option<&Chunk>,
-        neighbor_front: Option<&Chunk>,
-        neighbor_back: Option<&Chunk>,
-        atlas: &TextureAtlas,
-    ) -> Mesh {
-        let mut mesh = Mesh::empty();
+    /// Builds the faces of every block that are not hidden by another block.
+    /// Vertices are relative to the chunk, the mesh transform moves them into place.
+    pub fn generate_mesh(&self, neighbors: &ChunkNeighbors, atlas: &TextureAtlas) -> ChunkMesh {
+        let tiles = atlas.tiles();
+        let mut chunk_mesh = ChunkMesh::empty();
+        chunk_mesh.visibility = ChunkVisibility::compute(self);
+        let mut neighbors = *neighbors;
+        neighbors.chunks[1][1][1] = Some(self);
+        let neighbors = &neighbors;
+
+        // Roughly one visible face per block, on the surface that's about right
+        let mut blocks = [0; RenderLayer::ALL.len()];
+        for block in self.data.iter().flatten().flatten() {
+            if *block != BlockId::Air {
+                blocks[block.render_layer() as usize] += 1;
+            }
+        }
+        for layer in RenderLayer::ALL {
+            chunk_mesh
+                .layer_mut(layer)
+                .reserve_quads(blocks[layer as usize]);
+        }
 
         for h in 0..CHUNK_HEIGHT {
             for w in 0..CHUNK_WIDTH {
@@ -66,85 +359,55 @@ impl Chunk {
                         continue;
                     }
                     let uv = atlas.get_block_uv(block_id);
-                    // Top
-                    if let Some(upper_layer) = self.data.get(h + 1) {
-                        if upper_layer[w][d] == BlockId::Air {
-                            self.add_upper_quad_for_block(&mut mesh, h, w, d, uv);
-                        }
-                    } else {
-                        self.add_upper_quad_for_block(&mut mesh, h, w, d, uv);
-                    }
+                    let mesh = chunk_mesh.layer_mut(block_id.render_layer());
+                    let (hi, wi, di) = (h as isize, w as isize, d as isize);
 
-                    if let Some(front_block) = self.data[h][w].get(d + 1) {
-                        if *front_block == BlockId::Air {
-                            self.add_front_quad_for_block(&mut mesh, h, w, d, uv);
-                        }
-                    } else {
-                        self.add_front_quad_for_block(&mut mesh, h, w, d, uv);
+                    if let Some(light) = Chunk::face_light_at(neighbors, block_id, hi + 1, wi, di) {
+                        let quad = Chunk::upper_quad_for_block(h, w, d, uv);
+                        Chunk::add_face(mesh, neighbors, [h, w, d], quad, light, tiles);
                     }
-
-                    if let Some(right_row) = self.data[h].get(w + 1) {
-                        if right_row[d] == BlockId::Air {
-                            self.add_right_quad_for_block(&mut mesh, h, w, d, uv);
-                        }
-                    } else {
-                        self.add_right_quad_for_block(&mut mesh, h, w, d, uv);
+                    if let Some(light) = Chunk::face_light_at(neighbors, block_id, hi, wi, di + 1) {
+                        let quad = Chunk::front_quad_for_block(h, w, d, uv);
+                        Chunk::add_face(mesh, neighbors, [h, w, d], quad, light, tiles);
                     }
-
-                    if let Some(back_block) =
-                        self.data[h][w].get(d.checked_sub(1).unwrap_or(CHUNK_DEPTH + 1))
-                    {
-                        if *back_block == BlockId::Air {
-                            self.add_backside_quad_for_block(&mut mesh, h, w, d, uv);
-                        }
-                    } else {
-                        self.add_backside_quad_for_block(&mut mesh, h, w, d, uv);
+                    if let Some(light) = Chunk::face_light_at(neighbors, block_id, hi, wi + 1, di) {
+                        let quad = Chunk::right_quad_for_block(h, w, d, uv);
+                        Chunk::add_face(mesh, neighbors, [h, w, d], quad, light, tiles);
                     }
-
-                    if let Some(left_row) =
-                        self.data[h].get(w.checked_sub(1).unwrap_or(CHUNK_WIDTH + 1))
-                    {
-                        if left_row[d] == BlockId::Air {
-                            self.add_left_quad_for_block(&mut mesh, h, w, d, uv);
-                        }
-                    } else {
-                        self.add_left_quad_for_block(&mut mesh, h, w, d, uv);
+                    if let Some(light) = Chunk::face_light_at(neighbors, block_id, hi, wi, di - 1) {
+                        let quad = Chunk::backside_quad_for_block(h, w, d, uv);
+                        Chunk::add_face(mesh, neighbors, [h, w, d], quad, light, tiles);
                     }
-
-                    if let Some(bottom_layer) =
-                        self.data.get(h.checked_sub(1).unwrap_or(CHUNK_HEIGHT + 1))
-                    {
-                        if bottom_layer[w][d] == BlockId::Air {
-                            self.add_bottom_quad_for_block(&mut mesh, h, w, d, uv);
-                        }
-                    } else {
-                        self.add_bottom_quad_for_block(&mut mesh, h, w, d, uv);
+                    if let Some(light) = Chunk::face_light_at(neighbors, block_id, hi, wi - 1, di) {
+                        let quad = Chunk::left_quad_for_block(h, w, d, uv);
+                        Chunk::add_face(mesh, neighbors, [h, w, d], quad, light, tiles);
+                    }
+                    if let Some(light) = Chunk::face_light_at(neighbors, block_id, hi - 1, wi, di) {
+                        let quad = Chunk::bottom_quad_for_block(h, w, d, uv);
+                        Chunk::add_face(mesh, neighbors, [h, w, d], quad, light, tiles);
                     }
                 }
             }
         }
 
-        mesh.transform = self.transform;
-        mesh.transform.position.x *= CHUNK_WIDTH as f32 - 1.0;
-        mesh.transform.position.y *= CHUNK_HEIGHT as f32;
-        mesh.transform.position.z *= CHUNK_DEPTH as f32 - 1.0;
+        let origin = self.origin();
+        for layer in RenderLayer::ALL {
+            let mesh = chunk_mesh.layer_mut(layer);
+            mesh.transform = self.transform;
+            mesh.transform.position.x = origin[0] as f32;
+            mesh.transform.position.y = origin[1] as f32;
+            mesh.transform.position.z = origin[2] as f32;
+        }
 
-        mesh
+        chunk_mesh
     }
 
-    fn add_upper_quad_for_block(
-        &self,
-        mesh: &mut Mesh,
-        h: usize,
-        w: usize,
-        d: usize,
-        uv: &BlockUv,
-    ) {
-        mesh.add_quad([
+    pub fn upper_quad_for_block(h: usize, w: usize, d: usize, uv: &BlockUv) -> [Vertex; 4] {
+        [
             Vertex::new(
-                self.transform.position.x + w as f32 + 0.5,
-                self.transform.position.y + h as f32 + 0.5,
-                self.transform.position.z + d as f32 - 0.5,
+                w as f32 + 0.5,
+                h as f32 + 0.5,
+                d as f32 - 0.5,
                 0.0,
                 1.0,
                 0.0,
@@ -152,9 +415,9 @@ impl Chunk {
                 uv.top[0].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 + 0.5,
-                self.transform.position.y + h as f32 + 0.5,
-                self.transform.position.z + d as f32 + 0.5,
+                w as f32 + 0.5,
+                h as f32 + 0.5,
+                d as f32 + 0.5,
                 0.0,
                 1.0,
                 0.0,
@@ -162,9 +425,9 @@ impl Chunk {
                 uv.top[1].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 - 0.5,
-                self.transform.position.y + h as f32 + 0.5,
-                self.transform.position.z + d as f32 + 0.5,
+                w as f32 - 0.5,
+                h as f32 + 0.5,
+                d as f32 + 0.5,
                 0.0,
                 1.0,
                 0.0,
@@ -172,30 +435,23 @@ impl Chunk {
                 uv.top[2].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 - 0.5,
-                self.transform.position.y + h as f32 + 0.5,
-                self.transform.position.z + d as f32 - 0.5,
+                w as f32 - 0.5,
+                h as f32 + 0.5,
+                d as f32 - 0.5,
                 0.0,
                 1.0,
                 0.0,
                 uv.top[3].uv_x,
                 uv.top[3].uv_y,
             ),
-        ]);
-    }
-    fn add_right_quad_for_block(
-        &self,
-        mesh: &mut Mesh,
-        h: usize,
-        w: usize,
-        d: usize,
-        uv: &BlockUv,
-    ) {
-        mesh.add_quad([
+        ]
+    }
+    pub fn right_quad_for_block(h: usize, w: usize, d: usize, uv: &BlockUv) -> [Vertex; 4] {
+        [
             Vertex::new(
-                self.transform.position.x + w as f32 + 0.5,
-                self.transform.position.y + h as f32 + 0.5,
-                self.transform.position.z + d as f32 - 0.5,
+                w as f32 + 0.5,
+                h as f32 + 0.5,
+                d as f32 - 0.5,
                 1.0,
                 0.0,
                 0.0,
@@ -203,9 +459,9 @@ impl Chunk {
                 uv.right[0].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 + 0.5,
-                self.transform.position.y + h as f32 - 0.5,
-                self.transform.position.z + d as f32 - 0.5,
+                w as f32 + 0.5,
+                h as f32 - 0.5,
+                d as f32 - 0.5,
                 1.0,
                 0.0,
                 0.0,
@@ -213,9 +469,9 @@ impl Chunk {
                 uv.right[1].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 + 0.5,
-                self.transform.position.y + h as f32 - 0.5,
-                self.transform.position.z + d as f32 + 0.5,
+                w as f32 + 0.5,
+                h as f32 - 0.5,
+                d as f32 + 0.5,
                 1.0,
                 0.0,
                 0.0,
@@ -223,30 +479,23 @@ impl Chunk {
                 uv.right[2].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 + 0.5,
-                self.transform.position.y + h as f32 + 0.5,
-                self.transform.position.z + d as f32 + 0.5,
+                w as f32 + 0.5,
+                h as f32 + 0.5,
+                d as f32 + 0.5,
                 1.0,
                 0.0,
                 0.0,
                 uv.right[3].uv_x,
                 uv.right[3].uv_y,
             ),
-        ]);
-    }
-    fn add_backside_quad_for_block(
-        &self,
-        mesh: &mut Mesh,
-        h: usize,
-        w: usize,
-        d: usize,
-        uv: &BlockUv,
-    ) {
-        mesh.add_quad([
+        ]
+    }
+    pub fn backside_quad_for_block(h: usize, w: usize, d: usize, uv: &BlockUv) -> [Vertex; 4] {
+        [
             Vertex::new(
-                self.transform.position.x + w as f32 - 0.5,
-                self.transform.position.y + h as f32 + 0.5,
-                self.transform.position.z + d as f32 - 0.5,
+                w as f32 - 0.5,
+                h as f32 + 0.5,
+                d as f32 - 0.5,
                 0.0,
                 0.0,
                 -1.0,
@@ -254,9 +503,9 @@ impl Chunk {
                 uv.back[0].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 - 0.5,
-                self.transform.position.y + h as f32 - 0.5,
-                self.transform.position.z + d as f32 - 0.5,
+                w as f32 - 0.5,
+                h as f32 - 0.5,
+                d as f32 - 0.5,
                 0.0,
                 0.0,
                 -1.0,
@@ -264,9 +513,9 @@ impl Chunk {
                 uv.back[1].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 + 0.5,
-                self.transform.position.y + h as f32 - 0.5,
-                self.transform.position.z + d as f32 - 0.5,
+                w as f32 + 0.5,
+                h as f32 - 0.5,
+                d as f32 - 0.5,
                 0.0,
                 0.0,
                 -1.0,
@@ -274,30 +523,23 @@ impl Chunk {
                 uv.back[2].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 + 0.5,
-                self.transform.position.y + h as f32 + 0.5,
-                self.transform.position.z + d as f32 - 0.5,
+                w as f32 + 0.5,
+                h as f32 + 0.5,
+                d as f32 - 0.5,
                 0.0,
                 0.0,
                 -1.0,
                 uv.back[3].uv_x,
                 uv.back[3].uv_y,
             ),
-        ])
+        ]
     }
-    fn add_front_quad_for_block(
-        &self,
-        mesh: &mut Mesh,
-        h: usize,
-        w: usize,
-        d: usize,
-        uv: &BlockUv,
-    ) {
-        mesh.add_quad([
+    pub fn front_quad_for_block(h: usize, w: usize, d: usize, uv: &BlockUv) -> [Vertex; 4] {
+        [
             Vertex::new(
-                self.transform.position.x + w as f32 + 0.5,
-                self.transform.position.y + h as f32 + 0.5,
-                self.transform.position.z + d as f32 + 0.5,
+                w as f32 + 0.5,
+                h as f32 + 0.5,
+                d as f32 + 0.5,
                 0.0,
                 0.0,
                 1.0,
@@ -305,9 +547,9 @@ impl Chunk {
                 uv.front[0].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 + 0.5,
-                self.transform.position.y + h as f32 - 0.5,
-                self.transform.position.z + d as f32 + 0.5,
+                w as f32 + 0.5,
+                h as f32 - 0.5,
+                d as f32 + 0.5,
                 0.0,
                 0.0,
                 1.0,
@@ -315,9 +557,9 @@ impl Chunk {
                 uv.front[1].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 - 0.5,
-                self.transform.position.y + h as f32 - 0.5,
-                self.transform.position.z + d as f32 + 0.5,
+                w as f32 - 0.5,
+                h as f32 - 0.5,
+                d as f32 + 0.5,
                 0.0,
                 0.0,
                 1.0,
@@ -325,24 +567,24 @@ impl Chunk {
                 uv.front[2].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 - 0.5,
-                self.transform.position.y + h as f32 + 0.5,
-                self.transform.position.z + d as f32 + 0.5,
+                w as f32 - 0.5,
+                h as f32 + 0.5,
+                d as f32 + 0.5,
                 0.0,
                 0.0,
                 1.0,
                 uv.front[3].uv_x,
                 uv.front[3].uv_y,
             ),
-        ]);
+        ]
     }
 
-    fn add_left_quad_for_block(&self, mesh: &mut Mesh, h: usize, w: usize, d: usize, uv: &BlockUv) {
-        mesh.add_quad([
+    pub fn left_quad_for_block(h: usize, w: usize, d: usize, uv: &BlockUv) -> [Vertex; 4] {
+        [
             Vertex::new(
-                self.transform.position.x + w as f32 - 0.5,
-                self.transform.position.y + h as f32 + 0.5,
-                self.transform.position.z + d as f32 + 0.5,
+                w as f32 - 0.5,
+                h as f32 + 0.5,
+                d as f32 + 0.5,
                 -1.0,
                 0.0,
                 0.0,
@@ -350,9 +592,9 @@ impl Chunk {
                 uv.left[0].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 - 0.5,
-                self.transform.position.y + h as f32 - 0.5,
-                self.transform.position.z + d as f32 + 0.5,
+                w as f32 - 0.5,
+                h as f32 - 0.5,
+                d as f32 + 0.5,
                 -1.0,
                 0.0,
                 0.0,
@@ -360,9 +602,9 @@ impl Chunk {
                 uv.left[1].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 - 0.5,
-                self.transform.position.y + h as f32 - 0.5,
-                self.transform.position.z + d as f32 - 0.5,
+                w as f32 - 0.5,
+                h as f32 - 0.5,
+                d as f32 - 0.5,
                 -1.0,
                 0.0,
                 0.0,
@@ -370,31 +612,24 @@ impl Chunk {
                 uv.left[2].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 - 0.5,
-                self.transform.position.y + h as f32 + 0.5,
-                self.transform.position.z + d as f32 - 0.5,
+                w as f32 - 0.5,
+                h as f32 + 0.5,
+                d as f32 - 0.5,
                 -1.0,
                 0.0,
                 0.0,
                 uv.left[3].uv_x,
                 uv.left[3].uv_y,
             ),
-        ]);
+        ]
     }
 
-    fn add_bottom_quad_for_block(
-        &self,
-        mesh: &mut Mesh,
-        h: usize,
-        w: usize,
-        d: usize,
-        uv: &BlockUv,
-    ) {
-        mesh.add_quad([
+    pub fn bottom_quad_for_block(h: usize, w: usize, d: usize, uv: &BlockUv) -> [Vertex; 4] {
+        [
             Vertex::new(
-                self.transform.position.x + w as f32 + 0.5,
-                self.transform.position.y + h as f32 - 0.5,
-                self.transform.position.z + d as f32 + 0.5,
+                w as f32 + 0.5,
+                h as f32 - 0.5,
+                d as f32 + 0.5,
                 0.0,
                 -1.0,
                 0.0,
@@ -402,9 +637,9 @@ impl Chunk {
                 uv.bottom[0].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 + 0.5,
-                self.transform.position.y + h as f32 - 0.5,
-                self.transform.position.z + d as f32 - 0.5,
+                w as f32 + 0.5,
+                h as f32 - 0.5,
+                d as f32 - 0.5,
                 0.0,
                 -1.0,
                 0.0,
@@ -412,9 +647,9 @@ impl Chunk {
                 uv.bottom[1].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 - 0.5,
-                self.transform.position.y + h as f32 - 0.5,
-                self.transform.position.z + d as f32 - 0.5,
+                w as f32 - 0.5,
+                h as f32 - 0.5,
+                d as f32 - 0.5,
                 0.0,
                 -1.0,
                 0.0,
@@ -422,15 +657,117 @@ impl Chunk {
                 uv.bottom[2].uv_y,
             ),
             Vertex::new(
-                self.transform.position.x + w as f32 - 0.5,
-                self.transform.position.y + h as f32 - 0.5,
-                self.transform.position.z + d as f32 + 0.5,
+                w as f32 - 0.5,
+                h as f32 - 0.5,
+                d as f32 + 0.5,
                 0.0,
                 -1.0,
                 0.0,
                 uv.bottom[3].uv_x,
                 uv.bottom[3].uv_y,
             ),
-        ]);
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{flat_generator::FlatGenerator, texture_atlas::TextureAtlasCoords, world::World};
+
+    fn uv() -> BlockUv {
+        let coords = [TextureAtlasCoords::new(0.0, 0.0); 4];
+        BlockUv {
+            top: coords,
+            bottom: coords,
+            left: coords,
+            right: coords,
+            front: coords,
+            back: coords,
+        }
+    }
+
+    // Eight empty chunks from (0, 0, 0) to (1, 1, 1)
+    fn world() -> World {
+        let generator = FlatGenerator::new(&[]);
+        let mut world = World::new(1);
+        for x in 0..2 {
+            for y in 0..2 {
+                for z in 0..2 {
+                    world.generate_chunk([x, y, z], &generator);
+                }
+            }
+        }
+        world
+    }
+
+    fn top_face_ao(world: &World, point: [i64; 3]) -> [u8; 4] {
+        let (position, [h, w, d]) = World::split_point(point);
+        let neighbors = world.neighbors(position);
+        let quad = Chunk::upper_quad_for_block(h, w, d, &uv());
+        Chunk::face_ao(&neighbors, [h, w, d], &quad)
+    }
+
+    #[test]
+    fn vertex_ao_values() {
+        assert_eq!(vertex_ao(false, false, false), 3);
+        assert_eq!(vertex_ao(false, false, true), 2);
+        assert_eq!(vertex_ao(true, false, false), 2);
+        assert_eq!(vertex_ao(false, true, true), 1);
+        assert_eq!(vertex_ao(true, true, false), 0);
+        assert_eq!(vertex_ao(true, true, true), 0);
+    }
+
+    #[test]
+    fn open_face_has_no_occlusion() {
+        let mut world = world();
+        world.set_block([4, 4, 4], BlockId::Stone);
+        assert_eq!(top_face_ao(&world, [4, 4, 4]), [3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn wall_next_to_face_darkens_one_edge() {
+        let mut world = world();
+        world.set_block([4, 4, 4], BlockId::Stone);
+        world.set_block([5, 5, 4], BlockId::Stone);
+        // Vertices 0 and 1 are on the +x edge
+        assert_eq!(top_face_ao(&world, [4, 4, 4]), [2, 2, 3, 3]);
+    }
+
+    #[test]
+    fn inner_corner_is_fully_occluded() {
+        let mut world = world();
+        world.set_block([4, 4, 4], BlockId::Stone);
+        world.set_block([5, 5, 4], BlockId::Stone);
+        world.set_block([4, 5, 5], BlockId::Stone);
+        // Vertex 1 is the +x +z corner
+        let ao = top_face_ao(&world, [4, 4, 4]);
+        assert_eq!(ao, [2, 0, 2, 3]);
+        assert!(!should_flip_quad(ao));
+    }
+
+    #[test]
+    fn corner_block_in_diagonal_chunk() {
+        let mut world = world();
+        world.set_block([15, 15, 15], BlockId::Stone);
+        world.set_block([16, 16, 16], BlockId::Stone);
+        assert_eq!(top_face_ao(&world, [15, 15, 15]), [3, 2, 3, 3]);
+    }
+
+    #[test]
+    fn corner_edit_dirties_diagonal_chunks() {
+        let mut world = world();
+        let dirty = world.set_block([16, 16, 16], BlockId::Stone);
+        assert!(dirty.contains(&[0, 0, 0]));
+        assert!(dirty.contains(&[0, 1, 0]));
+        assert_eq!(dirty.len(), 8);
+    }
+
+    #[test]
+    fn quad_flips_towards_brighter_diagonal() {
+        assert!(should_flip_quad([0, 3, 3, 3]));
+        assert!(should_flip_quad([3, 3, 2, 3]));
+        assert!(!should_flip_quad([3, 0, 3, 3]));
+        assert!(!should_flip_quad([3, 3, 3, 3]));
     }
 }
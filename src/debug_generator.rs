@@ -0,0 +1,49 @@
+use crate::{block::BlockId, world_generator::TerrainGenerator};
+
+// Height of the row of blocks.
+const DEBUG_LAYER_HEIGHT: i64 = 0;
+// Blocks are laid out every DEBUG_SPACING blocks so that every face is visible.
+const DEBUG_SPACING: i64 = 2;
+
+/// Lays out one of each block in a square grid starting at the world origin,
+/// with nothing else around. Handy for checking textures and meshing.
+pub struct DebugGenerator {
+    blocks: Vec<BlockId>,
+    grid_width: i64,
+}
+
+impl DebugGenerator {
+    pub fn new(blocks: &[BlockId]) -> DebugGenerator {
+        let blocks: Vec<BlockId> = blocks
+            .iter()
+            .copied()
+            .filter(|block| *block != BlockId::Air)
+            .collect();
+        let grid_width = (blocks.len() as f64).sqrt().ceil().max(1.0) as i64;
+        DebugGenerator { blocks, grid_width }
+    }
+}
+
+impl TerrainGenerator for DebugGenerator {
+    fn get_block_at(&self, point: [i64; 3]) -> BlockId {
+        if point[1] != DEBUG_LAYER_HEIGHT
+            || point[0] < 0
+            || point[2] < 0
+            || point[0] % DEBUG_SPACING != 0
+            || point[2] % DEBUG_SPACING != 0
+        {
+            return BlockId::Air;
+        }
+
+        let column = point[0] / DEBUG_SPACING;
+        let row = point[2] / DEBUG_SPACING;
+        if column >= self.grid_width {
+            return BlockId::Air;
+        }
+
+        self.blocks
+            .get((row * self.grid_width + column) as usize)
+            .copied()
+            .unwrap_or(BlockId::Air)
+    }
+}
@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::BlockId, generator_settings::GeneratorSettings, world_generator::TerrainGenerator,
+};
+
+/// `thickness` blocks of `block`, stacked bottom to top in `FlatGenerator::new`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlatLayer {
+    pub block: BlockId,
+    pub thickness: u32,
+}
+
+impl FlatLayer {
+    pub fn new(block: BlockId, thickness: u32) -> FlatLayer {
+        FlatLayer { block, thickness }
+    }
+
+    /// Stone with a bit of dirt and grass on top.
+    pub fn default_layers() -> Vec<FlatLayer> {
+        vec![
+            FlatLayer::new(BlockId::Stone, 124),
+            FlatLayer::new(BlockId::Dirt, 3),
+            FlatLayer::new(BlockId::Grass, 1),
+        ]
+    }
+}
+
+/// Superflat terrain: the same column of layers everywhere, air above and below them.
+pub struct FlatGenerator {
+    column: Vec<BlockId>,
+    /// Y right above the top layer.
+    top: i64,
+    /// Nothing is generated below this.
+    min_y: i64,
+}
+
+impl FlatGenerator {
+    /// The top layer ends right below y = 0.
+    pub fn new(layers: &[FlatLayer]) -> FlatGenerator {
+        let mut column = Vec::new();
+        for layer in layers {
+            column.extend(std::iter::repeat_n(layer.block, layer.thickness as usize));
+        }
+        FlatGenerator {
+            column,
+            top: 0,
+            min_y: i64::MIN,
+        }
+    }
+
+    /// The `flat_layers` of `settings`, ending right below `sea_level` and cut off at `min_y`.
+    pub fn with_settings(settings: &GeneratorSettings) -> FlatGenerator {
+        FlatGenerator {
+            top: settings.sea_level,
+            min_y: settings.min_y,
+            ..FlatGenerator::new(&settings.flat_layers)
+        }
+    }
+
+    /// Number of non-air blocks in every column.
+    pub fn height(&self) -> i64 {
+        self.column.len() as i64
+    }
+}
+
+impl Default for FlatGenerator {
+    fn default() -> Self {
+        FlatGenerator::new(&FlatLayer::default_layers())
+    }
+}
+
+impl TerrainGenerator for FlatGenerator {
+    fn get_block_at(&self, point: [i64; 3]) -> BlockId {
+        if point[1] < self.min_y {
+            return BlockId::Air;
+        }
+        usize::try_from(point[1] - self.top + self.height())
+            .ok()
+            .and_then(|y| self.column.get(y))
+            .copied()
+            .unwrap_or(BlockId::Air)
+    }
+}
@@ -0,0 +1,219 @@
+use nalgebra_glm as glm;
+
+use crate::{camera::Camera, transform::Transform};
+
+/// Axis aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: glm::Vec3, max: glm::Vec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /// Smallest box holding every point, `None` if there are none.
+    pub fn from_points(points: impl IntoIterator<Item = glm::Vec3>) -> Option<Aabb> {
+        points.into_iter().fold(None, |aabb, point| {
+            Some(match aabb {
+                None => Aabb::new(point, point),
+                Some(aabb) => Aabb::new(aabb.min.inf(&point), aabb.max.sup(&point)),
+            })
+        })
+    }
+
+    pub fn corners(&self) -> [glm::Vec3; 8] {
+        std::array::from_fn(|i| {
+            glm::vec3(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            )
+        })
+    }
+
+    /// Box around this one after it is moved by `matrix`.
+    pub fn transformed(&self, matrix: &glm::Mat4) -> Aabb {
+        Aabb::from_points(
+            self.corners()
+                .map(|corner| (matrix * corner.push(1.0)).xyz()),
+        )
+        .unwrap()
+    }
+}
+
+/// The six planes around what a camera can see. Each plane is `(a, b, c, d)` with the
+/// normal `(a, b, c)` pointing inside, so a point `p` is on the inner side when
+/// `a * p.x + b * p.y + c * p.z + d >= 0`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far.
+    pub planes: [glm::Vec4; 6],
+}
+
+impl Frustum {
+    /// Pulls the planes out of `projection * view`, the Gribb-Hartmann way.
+    /// Expects OpenGL clip space, with depth going from -1 to 1.
+    pub fn from_matrix(view_projection: &glm::Mat4) -> Frustum {
+        let row = |i: usize| -> glm::Vec4 { view_projection.row(i).transpose() };
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(3) + row(2),
+            row(3) - row(2),
+        ];
+        Frustum {
+            planes: planes.map(|plane| plane / plane.xyz().norm()),
+        }
+    }
+
+    pub fn from_camera(camera: &dyn Camera, camera_transform: Transform) -> Frustum {
+        Frustum::from_matrix(&(camera.projection() * camera.view(camera_transform)))
+    }
+
+    pub fn contains_point(&self, point: &glm::Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(point) + plane.w >= 0.0)
+    }
+
+    /// Whether any part of the box may be visible. Boxes right outside a corner of the frustum
+    /// can pass too, which only costs a draw call that didn't need to happen.
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // Corner of the box the furthest along the plane normal
+            let corner = glm::vec3(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.xyz().dot(&corner) + plane.w >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_plane_eq(plane: glm::Vec4, expected: [f32; 4]) {
+        let expected = glm::Vec4::from(expected);
+        assert!(
+            (plane - expected).norm() < 1e-5 * expected.norm().max(1.0),
+            "expected {:?}, got {:?}",
+            expected,
+            plane
+        );
+    }
+
+    fn unit_box(center: glm::Vec3) -> Aabb {
+        Aabb::new(center.add_scalar(-0.5), center.add_scalar(0.5))
+    }
+
+    // 90 degrees field of view, square, looking down -z from the origin
+    fn perspective() -> Frustum {
+        let projection = glm::perspective(1.0, std::f32::consts::FRAC_PI_2, 0.1, 100.0);
+        Frustum::from_matrix(&projection)
+    }
+
+    #[test]
+    fn identity_gives_clip_space_cube() {
+        let frustum = Frustum::from_matrix(&glm::identity());
+        assert_plane_eq(frustum.planes[0], [1.0, 0.0, 0.0, 1.0]);
+        assert_plane_eq(frustum.planes[1], [-1.0, 0.0, 0.0, 1.0]);
+        assert_plane_eq(frustum.planes[2], [0.0, 1.0, 0.0, 1.0]);
+        assert_plane_eq(frustum.planes[3], [0.0, -1.0, 0.0, 1.0]);
+        assert_plane_eq(frustum.planes[4], [0.0, 0.0, 1.0, 1.0]);
+        assert_plane_eq(frustum.planes[5], [0.0, 0.0, -1.0, 1.0]);
+    }
+
+    #[test]
+    fn perspective_planes() {
+        let frustum = perspective();
+        let side = std::f32::consts::FRAC_1_SQRT_2;
+        assert_plane_eq(frustum.planes[0], [side, 0.0, -side, 0.0]);
+        assert_plane_eq(frustum.planes[1], [-side, 0.0, -side, 0.0]);
+        assert_plane_eq(frustum.planes[2], [0.0, side, -side, 0.0]);
+        assert_plane_eq(frustum.planes[3], [0.0, -side, -side, 0.0]);
+        assert_plane_eq(frustum.planes[4], [0.0, 0.0, -1.0, -0.1]);
+        assert_plane_eq(frustum.planes[5], [0.0, 0.0, 1.0, 100.0]);
+    }
+
+    #[test]
+    fn points() {
+        let frustum = perspective();
+        assert!(frustum.contains_point(&glm::vec3(0.0, 0.0, -10.0)));
+        assert!(frustum.contains_point(&glm::vec3(9.0, -9.0, -10.0)));
+        assert!(!frustum.contains_point(&glm::vec3(11.0, 0.0, -10.0)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, 10.0)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, -0.05)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, -101.0)));
+    }
+
+    #[test]
+    fn boxes() {
+        let frustum = perspective();
+        assert!(frustum.intersects(&unit_box(glm::vec3(0.0, 0.0, -10.0))));
+        // Behind the camera, out to the side, past the far plane
+        assert!(!frustum.intersects(&unit_box(glm::vec3(0.0, 0.0, 10.0))));
+        assert!(!frustum.intersects(&unit_box(glm::vec3(-20.0, 0.0, -10.0))));
+        assert!(!frustum.intersects(&unit_box(glm::vec3(0.0, 0.0, -102.0))));
+        // Sticking in through a side or through the near plane
+        assert!(frustum.intersects(&unit_box(glm::vec3(10.4, 0.0, -10.0))));
+        assert!(frustum.intersects(&unit_box(glm::vec3(0.0, 0.0, 0.0))));
+        // Bigger than the whole frustum
+        let huge = Aabb::new(
+            glm::vec3(-500.0, -500.0, -500.0),
+            glm::vec3(500.0, 500.0, 500.0),
+        );
+        assert!(frustum.intersects(&huge));
+    }
+
+    #[test]
+    fn boxes_with_view() {
+        // Standing at (0, 0, 10) looking down +x
+        let view = glm::look_at_rh(
+            &glm::vec3(0.0, 0.0, 10.0),
+            &glm::vec3(1.0, 0.0, 10.0),
+            &glm::Vec3::y(),
+        );
+        let projection = glm::perspective(1.0, std::f32::consts::FRAC_PI_2, 0.1, 100.0);
+        let frustum = Frustum::from_matrix(&(projection * view));
+        assert!(frustum.intersects(&unit_box(glm::vec3(20.0, 0.0, 10.0))));
+        assert!(!frustum.intersects(&unit_box(glm::vec3(-20.0, 0.0, 10.0))));
+        assert!(!frustum.intersects(&unit_box(glm::vec3(0.0, 0.0, -10.0))));
+    }
+
+    #[test]
+    fn box_from_points_and_transform() {
+        let aabb = Aabb::from_points([
+            glm::vec3(1.0, -2.0, 3.0),
+            glm::vec3(-1.0, 5.0, 0.0),
+            glm::vec3(0.0, 0.0, 4.0),
+        ])
+        .unwrap();
+        assert_eq!(aabb.min, glm::vec3(-1.0, -2.0, 0.0));
+        assert_eq!(aabb.max, glm::vec3(1.0, 5.0, 4.0));
+        assert_eq!(Aabb::from_points([]), None);
+
+        let moved = aabb.transformed(&glm::translation(&glm::vec3(16.0, 0.0, -16.0)));
+        assert_eq!(moved.min, glm::vec3(15.0, -2.0, -16.0));
+        assert_eq!(moved.max, glm::vec3(17.0, 5.0, -12.0));
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+
+/// How far a stick has to be pushed before it does anything, worn sticks never quite center.
+pub const STICK_DEADZONE: f32 = 0.15;
+
+/// How far a trigger has to be pulled to count as pressed.
+pub const TRIGGER_THRESHOLD: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamepadButton {
+    /// A on Xbox pads, cross on PlayStation ones.
+    South,
+    /// B on Xbox pads, circle on PlayStation ones.
+    East,
+}
+
+/// What the game cares about of a gamepad event, without anything specific to gilrs.
+/// Sticks go from -1 to 1 with up and right positive, triggers from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamepadEvent {
+    Axis(GamepadAxis, f32),
+    Pressed(GamepadButton),
+    Released(GamepadButton),
+    Disconnected,
+}
+
+/// State of the gamepad, every connected pad feeds into the same one. Events don't say which pad
+/// they came from, so any pad disconnecting releases everything, even what's held on the others.
+/// Playing with one pad is all this is meant for.
+pub struct Gamepad {
+    axes: [f32; 6],
+    buttons: HashSet<GamepadButton>,
+    pub deadzone: f32,
+}
+
+impl Default for Gamepad {
+    fn default() -> Self {
+        Gamepad::new()
+    }
+}
+
+impl Gamepad {
+    pub fn new() -> Gamepad {
+        Gamepad {
+            axes: [0.0; 6],
+            buttons: HashSet::new(),
+            deadzone: STICK_DEADZONE,
+        }
+    }
+
+    pub fn process_event(&mut self, event: GamepadEvent) {
+        match event {
+            GamepadEvent::Axis(axis, value) => self.axes[axis as usize] = value,
+            GamepadEvent::Pressed(button) => {
+                self.buttons.insert(button);
+            }
+            GamepadEvent::Released(button) => {
+                self.buttons.remove(&button);
+            }
+            // Whatever was held stays held otherwise, and the player walks off on their own
+            GamepadEvent::Disconnected => {
+                *self = Gamepad {
+                    deadzone: self.deadzone,
+                    ..Gamepad::new()
+                }
+            }
+        }
+    }
+
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes[axis as usize]
+    }
+
+    /// Right and forward, past the deadzone.
+    pub fn left_stick(&self) -> [f32; 2] {
+        apply_deadzone(
+            [
+                self.axis(GamepadAxis::LeftStickX),
+                self.axis(GamepadAxis::LeftStickY),
+            ],
+            self.deadzone,
+        )
+    }
+
+    /// Right and up, past the deadzone.
+    pub fn right_stick(&self) -> [f32; 2] {
+        apply_deadzone(
+            [
+                self.axis(GamepadAxis::RightStickX),
+                self.axis(GamepadAxis::RightStickY),
+            ],
+            self.deadzone,
+        )
+    }
+
+    /// Actions held on the gamepad, right trigger breaks and left trigger places like the mouse.
+    pub fn actions(&self) -> impl Iterator<Item = Action> + '_ {
+        let triggers = [
+            (GamepadAxis::RightTrigger, Action::Break),
+            (GamepadAxis::LeftTrigger, Action::Place),
+        ]
+        .into_iter()
+        .filter(|(axis, _)| self.axis(*axis) >= TRIGGER_THRESHOLD)
+        .map(|(_, action)| action);
+        let buttons = self.buttons.iter().map(|button| match button {
+            GamepadButton::South => Action::Jump,
+            GamepadButton::East => Action::Sneak,
+        });
+        triggers.chain(buttons)
+    }
+}
+
+/// Drops a stick within `deadzone` of the center and scales the rest so it still starts at 0
+/// right past the deadzone and reaches 1 at full tilt. Round instead of per axis, so pushing
+/// diagonally doesn't snap to the axes.
+pub fn apply_deadzone(stick: [f32; 2], deadzone: f32) -> [f32; 2] {
+    let length = (stick[0] * stick[0] + stick[1] * stick[1]).sqrt();
+    if length <= deadzone {
+        return [0.0, 0.0];
+    }
+    let scaled = ((length - deadzone) / (1.0 - deadzone)).min(1.0);
+    [stick[0] / length * scaled, stick[1] / length * scaled]
+}
+
+/// Reads every gamepad gilrs can find.
+pub struct Gamepads {
+    gilrs: gilrs::Gilrs,
+}
+
+impl Gamepads {
+    pub fn new() -> Result<Gamepads, String> {
+        let gilrs = gilrs::Gilrs::new().map_err(|e| format!("Failed to set up gamepads: {}", e))?;
+        Ok(Gamepads { gilrs })
+    }
+
+    /// Everything that happened since the last poll.
+    pub fn poll(&mut self) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+        while let Some(gilrs::Event { event, .. }) = self.gilrs.next_event() {
+            events.extend(translate_event(event));
+        }
+        events
+    }
+}
+
+fn translate_event(event: gilrs::EventType) -> Option<GamepadEvent> {
+    use gilrs::{Axis, Button, EventType};
+    match event {
+        EventType::AxisChanged(axis, value, _) => {
+            let axis = match axis {
+                Axis::LeftStickX => GamepadAxis::LeftStickX,
+                Axis::LeftStickY => GamepadAxis::LeftStickY,
+                Axis::RightStickX => GamepadAxis::RightStickX,
+                Axis::RightStickY => GamepadAxis::RightStickY,
+                _ => return None,
+            };
+            Some(GamepadEvent::Axis(axis, value))
+        }
+        // Analog triggers show up as buttons with a value
+        EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+            Some(GamepadEvent::Axis(GamepadAxis::LeftTrigger, value))
+        }
+        EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+            Some(GamepadEvent::Axis(GamepadAxis::RightTrigger, value))
+        }
+        EventType::ButtonPressed(Button::South, _) => {
+            Some(GamepadEvent::Pressed(GamepadButton::South))
+        }
+        EventType::ButtonPressed(Button::East, _) => {
+            Some(GamepadEvent::Pressed(GamepadButton::East))
+        }
+        EventType::ButtonReleased(Button::South, _) => {
+            Some(GamepadEvent::Released(GamepadButton::South))
+        }
+        EventType::ButtonReleased(Button::East, _) => {
+            Some(GamepadEvent::Released(GamepadButton::East))
+        }
+        EventType::Disconnected => Some(GamepadEvent::Disconnected),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::{Actions, Bindings};
+    use crate::keyboard::Keyboard;
+
+    fn assert_stick_eq(stick: [f32; 2], expected: [f32; 2]) {
+        assert!(
+            (stick[0] - expected[0]).abs() < 1e-5 && (stick[1] - expected[1]).abs() < 1e-5,
+            "expected {:?}, got {:?}",
+            expected,
+            stick
+        );
+    }
+
+    #[test]
+    fn stick_inside_deadzone_is_centered() {
+        let mut gamepad = Gamepad::new();
+        gamepad.process_event(GamepadEvent::Axis(GamepadAxis::LeftStickX, 0.1));
+        gamepad.process_event(GamepadEvent::Axis(GamepadAxis::LeftStickY, -0.05));
+        assert_stick_eq(gamepad.left_stick(), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn stick_past_deadzone_is_rescaled() {
+        let mut gamepad = Gamepad::new();
+        gamepad.process_event(GamepadEvent::Axis(GamepadAxis::LeftStickY, 1.0));
+        assert_stick_eq(gamepad.left_stick(), [0.0, 1.0]);
+
+        let halfway = STICK_DEADZONE + (1.0 - STICK_DEADZONE) / 2.0;
+        gamepad.process_event(GamepadEvent::Axis(GamepadAxis::LeftStickY, -halfway));
+        assert_stick_eq(gamepad.left_stick(), [0.0, -0.5]);
+    }
+
+    #[test]
+    fn diagonal_stick_keeps_its_direction() {
+        let stick = apply_deadzone([1.0, 1.0], STICK_DEADZONE);
+        assert_stick_eq(stick, [0.5f32.sqrt(), 0.5f32.sqrt()]);
+    }
+
+    #[test]
+    fn triggers_break_and_place_past_threshold() {
+        let mut gamepad = Gamepad::new();
+        gamepad.process_event(GamepadEvent::Axis(GamepadAxis::RightTrigger, 0.3));
+        assert_eq!(gamepad.actions().count(), 0);
+
+        gamepad.process_event(GamepadEvent::Axis(GamepadAxis::RightTrigger, 0.9));
+        gamepad.process_event(GamepadEvent::Axis(GamepadAxis::LeftTrigger, 1.0));
+        let actions: Vec<Action> = gamepad.actions().collect();
+        assert_eq!(actions, vec![Action::Break, Action::Place]);
+    }
+
+    #[test]
+    fn disconnect_releases_everything() {
+        let mut gamepad = Gamepad::new();
+        gamepad.process_event(GamepadEvent::Axis(GamepadAxis::LeftStickX, 1.0));
+        gamepad.process_event(GamepadEvent::Pressed(GamepadButton::South));
+        gamepad.process_event(GamepadEvent::Disconnected);
+        assert_stick_eq(gamepad.left_stick(), [0.0, 0.0]);
+        assert_eq!(gamepad.actions().count(), 0);
+    }
+
+    #[test]
+    fn any_disconnect_releases_every_pad() {
+        let mut gamepad = Gamepad::new();
+        gamepad.deadzone = 0.3;
+        // One pad holds jump, then a second one gets unplugged
+        gamepad.process_event(GamepadEvent::Pressed(GamepadButton::South));
+        gamepad.process_event(GamepadEvent::Disconnected);
+        assert_eq!(gamepad.actions().count(), 0);
+        assert_eq!(gamepad.deadzone, 0.3);
+
+        // Until the first pad sends something again
+        gamepad.process_event(GamepadEvent::Pressed(GamepadButton::South));
+        assert_eq!(gamepad.actions().collect::<Vec<_>>(), [Action::Jump]);
+    }
+
+    #[test]
+    fn gamepad_feeds_actions() {
+        let mut gamepad = Gamepad::new();
+        gamepad.process_event(GamepadEvent::Axis(GamepadAxis::LeftStickX, -1.0));
+        gamepad.process_event(GamepadEvent::Axis(GamepadAxis::RightStickY, 1.0));
+        gamepad.process_event(GamepadEvent::Pressed(GamepadButton::South));
+
+        let mut actions = Actions::new();
+        actions.update(&Bindings::default(), &Keyboard::new(), &gamepad);
+        assert!(actions.is_active(Action::Jump));
+        assert!(!actions.is_active(Action::Sneak));
+        assert_stick_eq(actions.movement(), [-1.0, 0.0]);
+        assert_stick_eq(actions.look(), [0.0, 1.0]);
+
+        gamepad.process_event(GamepadEvent::Released(GamepadButton::South));
+        actions.update(&Bindings::default(), &Keyboard::new(), &gamepad);
+        assert!(!actions.is_active(Action::Jump));
+    }
+}
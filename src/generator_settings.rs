@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{chunk::CHUNK_HEIGHT, flat_generator::FlatLayer};
+
+/// Shape of the terrain made by `WorldGenerator`, and by `FlatGenerator` for flat worlds.
+/// Layer heights are in blocks relative to `sea_level`, negative ones are below it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneratorSettings {
+    /// Lowest Y of the world, a multiple of the chunk height.
+    pub min_y: i64,
+    /// Y right above the highest block of the world, a multiple of the chunk height.
+    pub max_y: i64,
+    /// Absolute Y the layers are measured from.
+    pub sea_level: i64,
+    /// Caves are carved below this height, all the way down to `min_y`.
+    pub rock_max_height: i64,
+    /// Solid dirt from `rock_max_height` up to here.
+    pub dirt_layer_height: i64,
+    /// Hills can't grow past this height.
+    pub hill_layer_height: i64,
+    /// How far hills rise above `dirt_layer_height` at most.
+    pub hill_max_height: i64,
+    /// Size of cave features, bigger is wider.
+    pub cave_noise_scale: f64,
+    /// Size of hills, bigger is wider.
+    pub hill_noise_scale: f64,
+    /// What a flat world is made of, bottom to top. The top layer ends right below `sea_level`.
+    pub flat_layers: Vec<FlatLayer>,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        GeneratorSettings {
+            min_y: -128,
+            max_y: 256,
+            sea_level: 0,
+            rock_max_height: -20,
+            dirt_layer_height: 0,
+            hill_layer_height: 28,
+            hill_max_height: 32,
+            cave_noise_scale: 5.0,
+            hill_noise_scale: 30.0,
+            flat_layers: FlatLayer::default_layers(),
+        }
+    }
+}
+
+impl GeneratorSettings {
+    /// Reads a preset file, anything left out falls back to the default.
+    pub fn load(path: &str) -> Result<GeneratorSettings, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read preset {}: {}", path, e))?;
+        let settings: GeneratorSettings = toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse preset {}: {}", path, e))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_y % CHUNK_HEIGHT as i64 != 0 || self.max_y % CHUNK_HEIGHT as i64 != 0 {
+            return Err(format!(
+                "min_y and max_y must be multiples of the chunk height of {}",
+                CHUNK_HEIGHT
+            ));
+        }
+        if self.min_y >= self.max_y {
+            return Err("min_y must be below max_y".to_owned());
+        }
+        if self.dirt_layer_height < self.rock_max_height {
+            return Err("dirt_layer_height must be at least rock_max_height".to_owned());
+        }
+        if self.hill_layer_height < self.dirt_layer_height {
+            return Err("hill_layer_height must be at least dirt_layer_height".to_owned());
+        }
+        if self.sea_level + self.rock_max_height < self.min_y {
+            return Err("rock_max_height must be above min_y".to_owned());
+        }
+        if self.sea_level + self.hill_layer_height > self.max_y {
+            return Err("hill_layer_height must fit below max_y".to_owned());
+        }
+        if self.hill_max_height < 0 {
+            return Err("hill_max_height can't be negative".to_owned());
+        }
+        if self.cave_noise_scale <= 0.0 || self.hill_noise_scale <= 0.0 {
+            return Err("Noise scales must be positive".to_owned());
+        }
+        Ok(())
+    }
+
+    /// What `validate` leaves out because only flat worlds care about it.
+    pub fn validate_flat(&self) -> Result<(), String> {
+        let height: i64 = self.flat_layers.iter().map(|l| l.thickness as i64).sum();
+        if self.sea_level - height < self.min_y || self.sea_level > self.max_y {
+            return Err("flat_layers must fit between min_y and sea_level".to_owned());
+        }
+        Ok(())
+    }
+
+    /// Chunk coordinates of the lowest and the highest chunk in a column.
+    pub fn chunk_y_range(&self) -> std::ops::Range<i64> {
+        self.min_y.div_euclid(CHUNK_HEIGHT as i64)..self.max_y.div_euclid(CHUNK_HEIGHT as i64)
+    }
+}
@@ -0,0 +1,118 @@
+use std::{ffi::c_void, rc::Rc};
+
+use glium::{
+    backend::{Backend, Context},
+    debug::DebugCallbackBehavior,
+    SwapBuffersError,
+};
+use khronos_egl as egl;
+
+/// `EGL_PLATFORM_SURFACELESS_MESA`, a display that isn't attached to any screen.
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+/// OpenGL context without a window or even a display server, rendering with whatever Mesa
+/// driver is around, llvmpipe on machines without a GPU. There is no default framebuffer,
+/// everything has to be drawn into textures.
+struct HeadlessBackend {
+    egl: egl::Instance<egl::Static>,
+    display: egl::Display,
+    context: egl::Context,
+    dimensions: (u32, u32),
+}
+
+impl HeadlessBackend {
+    fn new(dimensions: (u32, u32)) -> Result<HeadlessBackend, String> {
+        let egl = egl::Instance::new(egl::Static);
+        let display = unsafe {
+            egl.get_platform_display(
+                PLATFORM_SURFACELESS_MESA,
+                egl::DEFAULT_DISPLAY,
+                &[egl::ATTRIB_NONE],
+            )
+        }
+        .map_err(|e| format!("No surfaceless EGL display: {}", e))?;
+        egl.initialize(display)
+            .map_err(|e| format!("Failed to initialize EGL: {}", e))?;
+        egl.bind_api(egl::OPENGL_API)
+            .map_err(|e| format!("EGL has no OpenGL: {}", e))?;
+
+        let config = egl
+            .choose_first_config(
+                display,
+                &[
+                    egl::RENDERABLE_TYPE,
+                    egl::OPENGL_BIT,
+                    // Nothing to draw on but textures
+                    egl::SURFACE_TYPE,
+                    0,
+                    egl::NONE,
+                ],
+            )
+            .map_err(|e| format!("Failed to choose EGL config: {}", e))?
+            .ok_or("No EGL config supports OpenGL")?;
+        let context = egl
+            .create_context(
+                display,
+                config,
+                None,
+                &[
+                    egl::CONTEXT_MAJOR_VERSION,
+                    3,
+                    egl::CONTEXT_MINOR_VERSION,
+                    3,
+                    egl::CONTEXT_OPENGL_PROFILE_MASK,
+                    egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                    egl::NONE,
+                ],
+            )
+            .map_err(|e| format!("Failed to create OpenGL 3.3 context: {}", e))?;
+
+        Ok(HeadlessBackend {
+            egl,
+            display,
+            context,
+            dimensions,
+        })
+    }
+}
+
+unsafe impl Backend for HeadlessBackend {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        self.egl
+            .get_proc_address(symbol)
+            .map_or(std::ptr::null(), |f| f as *const c_void)
+    }
+
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    fn is_current(&self) -> bool {
+        self.egl.get_current_context() == Some(self.context)
+    }
+
+    unsafe fn make_current(&self) {
+        self.egl
+            .make_current(self.display, None, None, Some(self.context))
+            .expect("Failed to make headless context current");
+    }
+}
+
+impl Drop for HeadlessBackend {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_context(self.display, self.context);
+    }
+}
+
+/// Creates a context to render offscreen with, `dimensions` only matter to glium's idea of the
+/// default framebuffer, which can't be drawn to.
+pub fn create_context(dimensions: (u32, u32)) -> Result<Rc<Context>, String> {
+    let backend = HeadlessBackend::new(dimensions)?;
+    unsafe { Context::new(backend, false, DebugCallbackBehavior::Ignore) }
+        .map_err(|e| format!("Headless OpenGL context is unusable: {}", e))
+}
@@ -1,116 +1,218 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::Hash;
 
-use glium::glutin::event::VirtualKeyCode;
+use glium::glutin::event::{ElementState, MouseButton, VirtualKeyCode};
 
-struct KeyState {
-    just_pressed: bool,
-    pressed: bool,
-    just_released: bool,
-    released: bool,
+/// Which buttons of one kind are held, and which changed since the last `end_frame`.
+struct ButtonStates<T> {
+    pressed: HashSet<T>,
+    just_pressed: HashSet<T>,
+    just_released: HashSet<T>,
 }
 
+impl<T: Copy + Eq + Hash> ButtonStates<T> {
+    fn new() -> ButtonStates<T> {
+        ButtonStates {
+            pressed: HashSet::new(),
+            just_pressed: HashSet::new(),
+            just_released: HashSet::new(),
+        }
+    }
+
+    fn update(&mut self, button: T, state: ElementState) {
+        match state {
+            // Held keys repeat presses, only the first one counts
+            ElementState::Pressed => {
+                if self.pressed.insert(button) {
+                    self.just_pressed.insert(button);
+                }
+            }
+            ElementState::Released => {
+                if self.pressed.remove(&button) {
+                    self.just_released.insert(button);
+                }
+            }
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+    }
+}
+
+/// Keyboard and mouse button state. Events come in through `process_event` and
+/// `process_window_event`, `end_frame` has to be called once everything that frame is done
+/// looking at it.
 pub struct Keyboard {
-    states: HashMap<VirtualKeyCode, KeyState>,
+    keys: ButtonStates<VirtualKeyCode>,
+    buttons: ButtonStates<MouseButton>,
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Keyboard::new()
+    }
 }
 
 impl Keyboard {
     pub fn new() -> Keyboard {
         Keyboard {
-            states: HashMap::new(),
+            keys: ButtonStates::new(),
+            buttons: ButtonStates::new(),
         }
     }
 
     pub fn process_event(&mut self, ev: &glium::glutin::event::DeviceEvent) {
-        match ev {
-            glium::glutin::event::DeviceEvent::Key(key) => {
-                if key.virtual_keycode.is_some() {
-                    let keycode = key.virtual_keycode.unwrap();
-                    match key.state {
-                        glium::glutin::event::ElementState::Pressed => {
-                            if self.states.contains_key(&keycode)
-                                && self.states[&keycode].just_pressed
-                            {
-                                self.states
-                                    .entry(keycode)
-                                    .and_modify(|x| {
-                                        x.just_pressed = false;
-                                        x.pressed = true;
-                                        x.released = false;
-                                        x.just_released = false;
-                                    })
-                                    .or_insert(KeyState {
-                                        just_pressed: false,
-                                        pressed: true,
-                                        just_released: false,
-                                        released: false,
-                                    });
-                            } else {
-                                self.states
-                                    .entry(keycode)
-                                    .and_modify(|x| {
-                                        x.just_pressed = true;
-                                        x.pressed = true;
-                                        x.released = false;
-                                        x.just_released = false;
-                                    })
-                                    .or_insert(KeyState {
-                                        just_pressed: true,
-                                        pressed: true,
-                                        just_released: false,
-                                        released: false,
-                                    });
-                            }
-                        }
-                        glium::glutin::event::ElementState::Released => {
-                            if self.states[&keycode].just_released {
-                                self.states
-                                    .entry(keycode)
-                                    .and_modify(|x| {
-                                        x.just_pressed = false;
-                                        x.pressed = false;
-                                        x.released = true;
-                                        x.just_released = true;
-                                    })
-                                    .or_insert(KeyState {
-                                        just_pressed: false,
-                                        pressed: false,
-                                        just_released: true,
-                                        released: true,
-                                    });
-                            } else {
-                                self.states
-                                    .entry(keycode)
-                                    .and_modify(|x| {
-                                        x.just_pressed = false;
-                                        x.pressed = false;
-                                        x.released = true;
-                                        x.just_released = false;
-                                    })
-                                    .or_insert(KeyState {
-                                        just_pressed: false,
-                                        pressed: false,
-                                        just_released: false,
-                                        released: true,
-                                    });
-                            }
-                        }
-                    }
-                }
-            }
-            _ => (),
+        if let glium::glutin::event::DeviceEvent::Key(glium::glutin::event::KeyboardInput {
+            virtual_keycode: Some(keycode),
+            state,
+            ..
+        }) = ev
+        {
+            self.process_key(*keycode, *state);
+        }
+    }
+
+    /// Mouse buttons only come through window events with a `MouseButton` attached.
+    pub fn process_window_event(&mut self, ev: &glium::glutin::event::WindowEvent) {
+        if let glium::glutin::event::WindowEvent::MouseInput { state, button, .. } = ev {
+            self.process_mouse_button(*button, *state);
+        }
+    }
+
+    pub fn process_key(&mut self, keycode: VirtualKeyCode, state: ElementState) {
+        self.keys.update(keycode, state);
+    }
+
+    pub fn process_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        self.buttons.update(button, state);
+    }
+
+    /// Forgets what was just pressed and released, the next frame starts from here.
+    pub fn end_frame(&mut self) {
+        self.keys.end_frame();
+        self.buttons.end_frame();
+    }
+
+    pub fn is_key_pressed(&self, keycode: VirtualKeyCode) -> bool {
+        self.keys.pressed.contains(&keycode)
+    }
+    pub fn is_key_released(&self, keycode: VirtualKeyCode) -> bool {
+        !self.is_key_pressed(keycode)
+    }
+    pub fn is_key_just_pressed(&self, keycode: VirtualKeyCode) -> bool {
+        self.keys.just_pressed.contains(&keycode)
+    }
+    pub fn is_key_just_released(&self, keycode: VirtualKeyCode) -> bool {
+        self.keys.just_released.contains(&keycode)
+    }
+
+    pub fn is_mouse_button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons.pressed.contains(&button)
+    }
+    pub fn is_mouse_button_released(&self, button: MouseButton) -> bool {
+        !self.is_mouse_button_pressed(button)
+    }
+    pub fn is_mouse_button_just_pressed(&self, button: MouseButton) -> bool {
+        self.buttons.just_pressed.contains(&button)
+    }
+    pub fn is_mouse_button_just_released(&self, button: MouseButton) -> bool {
+        self.buttons.just_released.contains(&button)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glium::glutin::event::{DeviceEvent, DeviceId, KeyboardInput, WindowEvent};
+
+    #[allow(deprecated)]
+    fn key(keycode: VirtualKeyCode, state: ElementState) -> DeviceEvent {
+        DeviceEvent::Key(KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(keycode),
+            modifiers: Default::default(),
+        })
+    }
+
+    fn mouse(button: MouseButton, state: ElementState) -> WindowEvent<'static> {
+        #[allow(deprecated)]
+        WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state,
+            button,
+            modifiers: Default::default(),
         }
     }
 
-    pub fn is_key_pressed(&self, keycode: glium::glutin::event::VirtualKeyCode) -> bool {
-        self.states.contains_key(&keycode) && self.states[&keycode].pressed
+    #[test]
+    fn press_is_just_pressed_for_one_frame() {
+        let mut keyboard = Keyboard::new();
+        keyboard.process_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+        assert!(keyboard.is_key_pressed(VirtualKeyCode::W));
+        assert!(keyboard.is_key_just_pressed(VirtualKeyCode::W));
+
+        keyboard.end_frame();
+        assert!(keyboard.is_key_pressed(VirtualKeyCode::W));
+        assert!(!keyboard.is_key_just_pressed(VirtualKeyCode::W));
     }
-    pub fn is_key_released(&self, keycode: glium::glutin::event::VirtualKeyCode) -> bool {
-        self.states.contains_key(&keycode) && self.states[&keycode].released
+
+    #[test]
+    fn repeated_press_is_not_just_pressed() {
+        let mut keyboard = Keyboard::new();
+        keyboard.process_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+        keyboard.end_frame();
+        keyboard.process_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+        assert!(keyboard.is_key_pressed(VirtualKeyCode::W));
+        assert!(!keyboard.is_key_just_pressed(VirtualKeyCode::W));
     }
-    pub fn is_key_just_pressed(&self, keycode: glium::glutin::event::VirtualKeyCode) -> bool {
-        self.states.contains_key(&keycode) && self.states[&keycode].just_pressed
+
+    #[test]
+    fn release_is_just_released_for_one_frame() {
+        let mut keyboard = Keyboard::new();
+        keyboard.process_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+        keyboard.end_frame();
+        keyboard.process_event(&key(VirtualKeyCode::W, ElementState::Released));
+        assert!(keyboard.is_key_released(VirtualKeyCode::W));
+        assert!(keyboard.is_key_just_released(VirtualKeyCode::W));
+
+        keyboard.end_frame();
+        assert!(keyboard.is_key_released(VirtualKeyCode::W));
+        assert!(!keyboard.is_key_just_released(VirtualKeyCode::W));
     }
-    pub fn is_key_just_released(&self, keycode: glium::glutin::event::VirtualKeyCode) -> bool {
-        self.states.contains_key(&keycode) && self.states[&keycode].just_released
+
+    #[test]
+    fn release_of_unseen_key_is_ignored() {
+        let mut keyboard = Keyboard::new();
+        keyboard.process_event(&key(VirtualKeyCode::Space, ElementState::Released));
+        assert!(keyboard.is_key_released(VirtualKeyCode::Space));
+        assert!(!keyboard.is_key_just_released(VirtualKeyCode::Space));
+    }
+
+    #[test]
+    fn tap_within_a_frame_is_both_edges() {
+        let mut keyboard = Keyboard::new();
+        keyboard.process_event(&key(VirtualKeyCode::E, ElementState::Pressed));
+        keyboard.process_event(&key(VirtualKeyCode::E, ElementState::Released));
+        assert!(!keyboard.is_key_pressed(VirtualKeyCode::E));
+        assert!(keyboard.is_key_just_pressed(VirtualKeyCode::E));
+        assert!(keyboard.is_key_just_released(VirtualKeyCode::E));
+    }
+
+    #[test]
+    fn mouse_buttons_have_edges_too() {
+        let mut keyboard = Keyboard::new();
+        keyboard.process_window_event(&mouse(MouseButton::Left, ElementState::Pressed));
+        assert!(keyboard.is_mouse_button_pressed(MouseButton::Left));
+        assert!(keyboard.is_mouse_button_just_pressed(MouseButton::Left));
+        assert!(!keyboard.is_mouse_button_pressed(MouseButton::Right));
+
+        keyboard.end_frame();
+        keyboard.process_window_event(&mouse(MouseButton::Left, ElementState::Released));
+        assert!(keyboard.is_mouse_button_released(MouseButton::Left));
+        assert!(keyboard.is_mouse_button_just_released(MouseButton::Left));
+        assert!(!keyboard.is_mouse_button_just_pressed(MouseButton::Left));
     }
 }
@@ -0,0 +1,29 @@
+pub mod actions;
+pub mod block;
+pub mod camera;
+pub mod chunk;
+pub mod debug_generator;
+pub mod flat_generator;
+pub mod frustum;
+pub mod gamepad;
+pub mod generator_settings;
+pub mod headless;
+pub mod keyboard;
+pub mod kinematic_body;
+pub mod lighting;
+pub mod mesh;
+pub mod player;
+pub mod renderer;
+pub mod replay;
+pub mod settings;
+pub mod shader;
+pub mod simulation;
+pub mod sky;
+pub mod texture_atlas;
+pub mod transform;
+pub mod util;
+pub mod vector3;
+pub mod vertex;
+pub mod visibility;
+pub mod world;
+pub mod world_generator;
@@ -0,0 +1,313 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+    block::BlockId,
+    chunk::{CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH},
+    world::World,
+};
+
+pub const MAX_LIGHT: u8 = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightChannel {
+    /// Light coming straight down from the sky.
+    Sky,
+    /// Light given off by blocks.
+    Block,
+}
+
+const CHANNELS: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+
+const DOWN: [i64; 3] = [0, -1, 0];
+const DIRECTIONS: [[i64; 3]; 6] = [
+    [0, 1, 0],
+    DOWN,
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+fn offset(point: [i64; 3], direction: [i64; 3]) -> [i64; 3] {
+    [
+        point[0] + direction[0],
+        point[1] + direction[1],
+        point[2] + direction[2],
+    ]
+}
+
+/// Level a neighbor in `direction` gets from a block lit at `level`.
+/// Full sky light keeps going straight down without fading, like sunlight does.
+fn spread(channel: LightChannel, level: u8, direction: [i64; 3]) -> u8 {
+    if channel == LightChannel::Sky && direction == DOWN && level == MAX_LIGHT {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+/// Light is flood filled through the loaded chunks, block by block in world coordinates,
+/// so it crosses chunk borders on its own. Unloaded chunks stop it.
+impl World {
+    /// `None` if the chunk holding `point` is not loaded.
+    pub fn get_light(&self, channel: LightChannel, point: [i64; 3]) -> Option<u8> {
+        let (position, [h, w, d]) = World::split_point(point);
+        self.get_chunk(position)
+            .map(|chunk| chunk.get_light(channel, h, w, d))
+    }
+
+    fn set_light(
+        &mut self,
+        channel: LightChannel,
+        point: [i64; 3],
+        level: u8,
+        dirty: &mut HashSet<[i64; 3]>,
+    ) {
+        let (position, [h, w, d]) = World::split_point(point);
+        if let Some(chunk) = self.chunks.get_mut(&position) {
+            chunk.set_light(channel, h, w, d, level);
+            World::mark_dirty(point, dirty);
+        }
+    }
+
+    /// Lights every loaded chunk from scratch.
+    pub fn compute_light(&mut self) {
+        for chunk in self.chunks.values_mut() {
+            chunk.clear_light();
+        }
+
+        let mut dirty = HashSet::new();
+        let mut sky = VecDeque::new();
+        let mut block = VecDeque::new();
+
+        // Sky light pours down every column until something opaque gets in the way
+        let positions: Vec<[i64; 3]> = self.chunks.keys().copied().collect();
+        for position in &positions {
+            if self.chunks.contains_key(&offset(*position, [0, 1, 0])) {
+                continue;
+            }
+            let top = self.get_chunk(*position).unwrap().origin();
+            for x in top[0]..top[0] + CHUNK_WIDTH as i64 {
+                for z in top[2]..top[2] + CHUNK_DEPTH as i64 {
+                    let mut point = [x, top[1] + CHUNK_HEIGHT as i64 - 1, z];
+                    while let Some(block) = self.get_block(point) {
+                        if block.is_opaque() {
+                            break;
+                        }
+                        self.set_light(LightChannel::Sky, point, MAX_LIGHT, &mut dirty);
+                        sky.push_back(point);
+                        point = offset(point, DOWN);
+                    }
+                }
+            }
+        }
+
+        for position in &positions {
+            let origin = self.get_chunk(*position).unwrap().origin();
+            for point in World::chunk_points(origin) {
+                let emission = self.get_block(point).unwrap().light_emission();
+                if emission > 0 {
+                    self.set_light(LightChannel::Block, point, emission, &mut dirty);
+                    block.push_back(point);
+                }
+            }
+        }
+
+        self.propagate_light(LightChannel::Sky, sky, &mut dirty);
+        self.propagate_light(LightChannel::Block, block, &mut dirty);
+    }
+
+    /// Fixes up the light around `point` after its block went from `old` to `new`.
+    pub(crate) fn update_light(
+        &mut self,
+        point: [i64; 3],
+        old: BlockId,
+        new: BlockId,
+        dirty: &mut HashSet<[i64; 3]>,
+    ) {
+        for channel in CHANNELS {
+            let level = self.get_light(channel, point).unwrap_or(0);
+            let emission = match channel {
+                LightChannel::Sky => 0,
+                LightChannel::Block => new.light_emission(),
+            };
+
+            let mut relight = VecDeque::new();
+            if level > 0
+                && (new.is_opaque()
+                    || (channel == LightChannel::Block && old.light_emission() > emission))
+            {
+                self.set_light(channel, point, 0, dirty);
+                relight = self.remove_light(channel, VecDeque::from([(point, level)]), dirty);
+            }
+
+            if emission > self.get_light(channel, point).unwrap_or(0) {
+                self.set_light(channel, point, emission, dirty);
+                relight.push_back(point);
+            }
+
+            if !new.is_opaque() {
+                // Let the surroundings shine back in
+                for direction in DIRECTIONS {
+                    let neighbor = offset(point, direction);
+                    match self.get_light(channel, neighbor) {
+                        Some(level) if level > 0 => relight.push_back(neighbor),
+                        // Nothing loaded above, that's open sky
+                        None if channel == LightChannel::Sky && direction == [0, 1, 0] => {
+                            self.set_light(channel, point, MAX_LIGHT, dirty);
+                            relight.push_back(point);
+                        }
+                        _ => (),
+                    }
+                }
+            }
+
+            self.propagate_light(channel, relight, dirty);
+        }
+    }
+
+    /// Spreads light out of every point in `queue` into the darker blocks around it.
+    fn propagate_light(
+        &mut self,
+        channel: LightChannel,
+        mut queue: VecDeque<[i64; 3]>,
+        dirty: &mut HashSet<[i64; 3]>,
+    ) {
+        while let Some(point) = queue.pop_front() {
+            let Some(level) = self.get_light(channel, point) else {
+                continue;
+            };
+            for direction in DIRECTIONS {
+                let neighbor = offset(point, direction);
+                let Some(block) = self.get_block(neighbor) else {
+                    continue;
+                };
+                if block.is_opaque() {
+                    continue;
+                }
+                let new_level = spread(channel, level, direction);
+                if new_level > self.get_light(channel, neighbor).unwrap() {
+                    self.set_light(channel, neighbor, new_level, dirty);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+
+    /// Darkens everything that was lit by the points in `queue`, each with the level it had.
+    /// Returns the points bordering the darkened area that still have light of their own,
+    /// which have to be propagated again to fill it back in.
+    fn remove_light(
+        &mut self,
+        channel: LightChannel,
+        mut queue: VecDeque<([i64; 3], u8)>,
+        dirty: &mut HashSet<[i64; 3]>,
+    ) -> VecDeque<[i64; 3]> {
+        let mut relight = VecDeque::new();
+        while let Some((point, level)) = queue.pop_front() {
+            for direction in DIRECTIONS {
+                let neighbor = offset(point, direction);
+                let Some(neighbor_level) = self.get_light(channel, neighbor) else {
+                    continue;
+                };
+                let lit_by_point = neighbor_level != 0
+                    && (neighbor_level < level
+                        || spread(channel, level, direction) == neighbor_level);
+                if lit_by_point {
+                    self.set_light(channel, neighbor, 0, dirty);
+                    queue.push_back((neighbor, neighbor_level));
+
+                    let emission = match channel {
+                        LightChannel::Sky => 0,
+                        LightChannel::Block => self.get_block(neighbor).unwrap().light_emission(),
+                    };
+                    if emission > 0 {
+                        self.set_light(channel, neighbor, emission, dirty);
+                        relight.push_back(neighbor);
+                    }
+                } else if neighbor_level >= level {
+                    relight.push_back(neighbor);
+                }
+            }
+        }
+        relight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flat_generator::{FlatGenerator, FlatLayer};
+
+    // Two chunks side by side along x, stone floor at y = -1, air above
+    fn world() -> World {
+        let generator = FlatGenerator::new(&[FlatLayer::new(BlockId::Stone, 16)]);
+        let mut world = World::new(1);
+        for x in 0..2 {
+            for y in -1..1 {
+                world.generate_chunk([x, y, 0], &generator);
+            }
+        }
+        world.compute_light();
+        world
+    }
+
+    #[test]
+    fn block_light_crosses_chunk_border() {
+        let mut world = world();
+        world.set_block([13, 4, 8], BlockId::Lamp);
+
+        assert_eq!(world.get_light(LightChannel::Block, [13, 4, 8]), Some(15));
+        assert_eq!(world.get_light(LightChannel::Block, [15, 4, 8]), Some(13));
+        assert_eq!(world.get_light(LightChannel::Block, [16, 4, 8]), Some(12));
+        assert_eq!(world.get_light(LightChannel::Block, [20, 5, 9]), Some(6));
+        // Stone doesn't get lit
+        assert_eq!(world.get_light(LightChannel::Block, [13, -1, 8]), Some(0));
+    }
+
+    #[test]
+    fn removing_lamp_darkens_neighbor_chunk() {
+        let mut world = world();
+        world.set_block([13, 4, 8], BlockId::Lamp);
+        world.set_block([20, 4, 8], BlockId::Lamp);
+        world.set_block([13, 4, 8], BlockId::Air);
+
+        assert_eq!(world.get_light(LightChannel::Block, [13, 4, 8]), Some(8));
+        assert_eq!(world.get_light(LightChannel::Block, [16, 4, 8]), Some(11));
+        assert_eq!(world.get_light(LightChannel::Block, [20, 4, 8]), Some(15));
+
+        world.set_block([20, 4, 8], BlockId::Air);
+        for x in 0..32 {
+            assert_eq!(world.get_light(LightChannel::Block, [x, 4, 8]), Some(0));
+        }
+    }
+
+    #[test]
+    fn sky_light_is_blocked_and_restored() {
+        let mut world = world();
+        assert_eq!(world.get_light(LightChannel::Sky, [15, 0, 8]), Some(15));
+
+        // Roof over the whole world, right on the border between the two chunk layers
+        for x in 0..32 {
+            for z in 0..16 {
+                world.set_block([x, 0, z], BlockId::Stone);
+            }
+        }
+        for x in 0..32 {
+            assert_eq!(world.get_light(LightChannel::Sky, [x, 15, 8]), Some(15));
+            assert_eq!(world.get_light(LightChannel::Sky, [x, 0, 8]), Some(0));
+        }
+
+        // Single hole, sunlight goes through it and fades sideways under the roof
+        world.set_block([16, 0, 8], BlockId::Air);
+        world.set_block([16, -1, 8], BlockId::Air);
+        world.set_block([15, -1, 8], BlockId::Air);
+        assert_eq!(world.get_light(LightChannel::Sky, [16, 0, 8]), Some(15));
+        assert_eq!(world.get_light(LightChannel::Sky, [16, -1, 8]), Some(15));
+        assert_eq!(world.get_light(LightChannel::Sky, [15, -1, 8]), Some(14));
+
+        world.set_block([16, 0, 8], BlockId::Stone);
+        assert_eq!(world.get_light(LightChannel::Sky, [16, -1, 8]), Some(0));
+        assert_eq!(world.get_light(LightChannel::Sky, [15, -1, 8]), Some(0));
+    }
+}
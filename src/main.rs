@@ -1,85 +1,97 @@
-pub mod block;
-pub mod camera;
-pub mod chunk;
-pub mod keyboard;
-pub mod kinematic_body;
-pub mod mesh;
-pub mod player;
-pub mod texture_atlas;
-pub mod transform;
-pub mod util;
-pub mod vector3;
-pub mod vertex;
-pub mod world;
-pub mod world_generator;
-
-use std::time::{SystemTime, UNIX_EPOCH};
-
-use block::BlockId;
-use chunk::Chunk;
-use mesh::Mesh;
-use transform::Transform;
-
-use glium::Surface;
-use world_generator::WorldGenerator;
-
-const VERTEX_SHADER_SRC: &str = r#"
-    #version 330
-    
-    in vec3 position;
-    in vec3 normal;
-    in vec2 uv;
-
-    uniform mat4 transform;
-    uniform mat4 view;
-    uniform mat4 projection;
-
-    out vec3 v_position;
-    out vec3 v_normal;
-    out vec2 v_uv;
-    out float v_depth;
-
-    void main() {
-        gl_Position = projection * view * transform * vec4(position, 1.0);
-
-        v_position = position;
-        v_normal = transpose(inverse(mat3(transform))) * normal;
-        v_uv = uv;
-        v_depth = gl_Position[3];
-    }
-"#;
-
-const FRAGMENT_SHADER_SRC: &str = r#"
-    #version 330
-
-    in vec3 v_position;
-    in vec3 v_normal;
-    in vec2 v_uv;
-    in float v_depth;
-
-    uniform sampler2D albedo;
-
-    out vec4 final_color;
-
-
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use mc_rs::{
+    actions::{Bindings, BINDINGS_PATH},
+    camera::{
+        Camera, CameraMode, MapCamera, OrthographicCamera, PerspectiveCamera3D, ThirdPersonCamera,
+    },
+    chunk::CHUNK_WIDTH,
+    gamepad::Gamepads,
+    generator_settings::GeneratorSettings,
+    player::MOUSE_LOOK_RATE,
+    renderer::Renderer,
+    replay::Recorder,
+    settings::{Settings, SETTINGS_PATH},
+    simulation::{InputEvent, Simulation, TickClock},
+    world::{WorldMetadata, WorldType},
+};
+
+use glium::glutin::{dpi::PhysicalSize, event::VirtualKeyCode, window::Fullscreen};
+
+const WORLD_METADATA_PATH: &str = "world/world.toml";
+
+const FULLSCREEN_KEY: VirtualKeyCode = VirtualKeyCode::F11;
+const RELOAD_SETTINGS_KEY: VirtualKeyCode = VirtualKeyCode::F8;
+const CAMERA_MODE_KEY: VirtualKeyCode = VirtualKeyCode::F5;
+
+fn resize(
+    display: &glium::Display,
+    camera: &mut PerspectiveCamera3D,
+    map: &mut OrthographicCamera,
+    size: PhysicalSize<u32>,
+) {
+    // Some platforms, Wayland for one, don't resize the surface on their own
+    display.gl_window().resize(size);
+    camera.set_viewport(size.width, size.height);
+    map.set_viewport(size.width, size.height);
+}
 
-    vec4 color_light = vec4(1.0, 1.0, 1.0, 1.0);
-    vec4 color_dark = vec4(0.4, 0.4, 0.45, 1.0);
-    vec3 direction_light = vec3(1.0, 10.0, 1.0);
+/// Switches between a window and borderless fullscreen on the current monitor.
+fn toggle_fullscreen(display: &glium::Display) {
+    let gl_window = display.gl_window();
+    let window = gl_window.window();
+    if window.fullscreen().is_some() {
+        window.set_fullscreen(None);
+    } else {
+        window.set_fullscreen(Some(Fullscreen::Borderless(window.current_monitor())));
+    }
+}
 
-    void main() {
-        final_color = texture(albedo, v_uv);
-        float light_amount = dot(normalize(v_normal), normalize(direction_light));
-        light_amount = clamp(light_amount, 0.0, 1.0);
+/// Reads the settings and bindings files again, keeping the old ones if either is broken.
+fn reload_settings(settings: &mut Settings, simulation: &mut Simulation) {
+    match Bindings::load(BINDINGS_PATH) {
+        Ok(new) => simulation.bindings = new,
+        Err(e) => eprintln!("{}", e),
+    }
+    let new = match Settings::load(SETTINGS_PATH) {
+        Ok(new) => new,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    if new.graphics.render_distance != settings.graphics.render_distance
+        || new.graphics.vsync != settings.graphics.vsync
+    {
+        println!("Render distance and vsync change after a restart");
+    }
+    simulation.player.apply_settings(&new);
+    *settings = new;
+    println!("Reloaded settings");
+}
 
-        final_color = mix(final_color * color_dark, final_color, light_amount);
+/// Hands `event` to the simulation, writing it down first if the session is being recorded.
+fn process_input(simulation: &mut Simulation, recorder: &mut Option<Recorder>, event: InputEvent) {
+    if let Some(recorder) = recorder {
+        recorder.record_event(simulation, event);
     }
-"#;
+    simulation.process_event(&event);
+}
 
 fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // A recording can only be replayed from a fresh world, so it has to start right away
+    let record_path = args.iter().position(|arg| arg == "--record").map(|i| {
+        args.remove(i);
+        assert!(i < args.len(), "--record needs a file to write to");
+        args.remove(i)
+    });
+
+    let mut settings = Settings::load_or_create(SETTINGS_PATH).unwrap_or_else(|e| panic!("{}", e));
+
     let cb = glium::glutin::ContextBuilder::new()
         .with_depth_buffer(24)
-        .with_vsync(true);
+        .with_vsync(settings.graphics.vsync);
     let wb = glium::glutin::window::WindowBuilder::new().with_title("Rustycube");
     let event_loop = glium::glutin::event_loop::EventLoop::new();
     println!("Created event loop");
@@ -98,133 +110,231 @@ fn main() {
         })
         .expect("Failed to grab cursor");
 
-    let shader_program = glium::program::Program::from_source(
-        &display,
-        VERTEX_SHADER_SRC,
-        FRAGMENT_SHADER_SRC,
-        None,
-    )
-    .expect("Failed to create shader program");
-
-    println!("Created shader program");
-
-    let atlas = texture_atlas::TextureAtlas::load(&display, "res/textures/blocks.png".to_owned())
-        .with_blocks(
-            6,
-            &vec![
-                // BlockId::Air,
-                BlockId::Dirt,
-                BlockId::Grass,
-                BlockId::Stone,
-                // BlockId::Sand,
-                // BlockId::Water,
-            ],
+    // Fog ends at the edge of the loaded area, with a chunk of slack
+    let fog_distance = (settings.graphics.render_distance + 1) as f32 * CHUNK_WIDTH as f32;
+    let mut renderer = Renderer::new(&display, fog_distance).unwrap_or_else(|e| panic!("{}", e));
+    println!("Created renderer");
+
+    let bindings = Bindings::load_or_create(BINDINGS_PATH).unwrap_or_else(|e| panic!("{}", e));
+    // Keyboard and mouse still work without gamepads
+    let mut gamepads = Gamepads::new().map_err(|e| eprintln!("{}", e)).ok();
+
+    let metadata = if std::path::Path::new(WORLD_METADATA_PATH).exists() {
+        // World type and preset only matter for a new world, the saved one keeps its own
+        if !args.is_empty() {
+            eprintln!(
+                "Ignoring {:?}, {} already exists. Delete it to make a new world",
+                args, WORLD_METADATA_PATH
+            );
+        }
+        WorldMetadata::load(WORLD_METADATA_PATH).expect("Failed to load world")
+    } else {
+        let mut args = args.into_iter();
+        let world_type: WorldType = args
+            .next()
+            .map(|arg| arg.parse().expect("Invalid world type"))
+            .unwrap_or_default();
+        let mut metadata = WorldMetadata::new(
+            {
+                let start = SystemTime::now();
+                start
+                    .duration_since(UNIX_EPOCH)
+                    .expect("King crimson is among us")
+                    .as_millis() as u32
+            },
+            world_type,
         );
-
-    let mut keyboard_input = keyboard::Keyboard::new();
-    let mut player = player::Player::new(8.0, 180.0, 50.0, 10.0);
-    player.transform.position.z = 5.0;
-
-    let mut chunk = Chunk::new(&Transform::zero());
-    println!("Create empty root chunk");
-    let generator = WorldGenerator::new({
-        let start = SystemTime::now();
-        start
-            .duration_since(UNIX_EPOCH)
-            .expect("King crimson is among us")
-            .as_millis() as u32
-    });
-    println!("Set up world generator");
-    chunk.generate_data(&generator);
-    println!("Generated chunk data");
-    let mut chunk_mesh = chunk.generate_mesh(None, None, None, None, &atlas);
-    println!("Generated chunk mesh");
-    chunk_mesh.build(&display);
-
-    let mut chunks: Vec<Chunk> = Vec::new();
-    let mut chunk_meshes: Vec<Mesh> = Vec::new();
-    let render_distance = 1;
-
-    for i in 0..render_distance {
-        for j in 0..render_distance {
-            let mut transform = Transform::zero();
-            transform.position.x = i as f32;
-            transform.position.z = j as f32;
-            let mut chunk = Chunk::new(&transform);
-
-            chunk.generate_data(&generator);
-            let mut chunk_mesh = chunk.generate_mesh(None, None, None, None, &atlas);
-            chunk_mesh.build(&display);
-
-            chunk_meshes.push(chunk_mesh);
-            chunks.push(chunk);
+        if let Some(preset) = args.next() {
+            metadata.generator_settings =
+                GeneratorSettings::load(&preset).expect("Failed to load generator preset");
         }
-    }
-
-    let mut delta: f32 = 1.0 / 120.0;
-    player.transform.rotate_y(-90.0f32.to_radians());
-    player.transform.position.y = 130.0;
+        metadata.validate().unwrap_or_else(|e| panic!("{}", e));
+        metadata
+            .save(WORLD_METADATA_PATH)
+            .expect("Failed to save world");
+        metadata
+    };
+    println!("Generating {:?} world", metadata.world_type);
+
+    let mut simulation = Simulation::new(&metadata, &settings, bindings);
+    println!("Generated world");
+    let size = display.gl_window().window().inner_size();
+    simulation
+        .player
+        .camera
+        .set_viewport(size.width, size.height);
+    let mut map = MapCamera::default();
+    map.camera.set_viewport(size.width, size.height);
+
+    renderer.mesh_world(&display, &simulation.world);
+    println!("Generated chunk meshes");
+
+    let mut recorder = record_path
+        .as_ref()
+        .map(|_| Recorder::new(&metadata, &settings, &simulation.bindings));
+    let mut camera_mode = CameraMode::default();
+    let mut third_person = ThirdPersonCamera::default();
+    // Holding alt in third person swings the camera around the player with the mouse, instead
+    // of turning them
+    let mut orbiting = false;
+    let mut clock = TickClock::new();
+    let mut last_frame = Instant::now();
+    let mut next_frame = last_frame;
 
     event_loop.run(move |ev, _, control_flow| {
-        let frame_start = std::time::Instant::now();
-        let next_frame_time = frame_start + std::time::Duration::from_millis(1000 / 120);
-        *control_flow = glium::glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
-
-        *control_flow = glium::glutin::event_loop::ControlFlow::Poll;
-
         match &ev {
             glium::glutin::event::Event::WindowEvent { event, .. } => match event {
                 glium::glutin::event::WindowEvent::CloseRequested => {
+                    if let (Some(recorder), Some(path)) = (recorder.take(), &record_path) {
+                        match recorder.finish(&simulation).save(path) {
+                            Ok(()) => println!("Saved recording to {}", path),
+                            Err(e) => eprintln!("{}", e),
+                        }
+                    }
                     *control_flow = glium::glutin::event_loop::ControlFlow::Exit;
-                    return;
                 }
-                _ => return,
+                glium::glutin::event::WindowEvent::Resized(size) => {
+                    resize(
+                        &display,
+                        &mut simulation.player.camera,
+                        &mut map.camera,
+                        *size,
+                    );
+                }
+                glium::glutin::event::WindowEvent::ScaleFactorChanged {
+                    new_inner_size, ..
+                } => {
+                    resize(
+                        &display,
+                        &mut simulation.player.camera,
+                        &mut map.camera,
+                        **new_inner_size,
+                    );
+                }
+                glium::glutin::event::WindowEvent::ModifiersChanged(modifiers) => {
+                    orbiting = modifiers.alt();
+                    if !orbiting {
+                        third_person.reset_orbit();
+                    }
+                }
+                glium::glutin::event::WindowEvent::KeyboardInput {
+                    input:
+                        glium::glutin::event::KeyboardInput {
+                            state: glium::glutin::event::ElementState::Pressed,
+                            virtual_keycode: Some(FULLSCREEN_KEY),
+                            ..
+                        },
+                    ..
+                } => {
+                    toggle_fullscreen(&display);
+                }
+                glium::glutin::event::WindowEvent::KeyboardInput {
+                    input:
+                        glium::glutin::event::KeyboardInput {
+                            state: glium::glutin::event::ElementState::Pressed,
+                            virtual_keycode: Some(RELOAD_SETTINGS_KEY),
+                            ..
+                        },
+                    ..
+                } => {
+                    if recorder.is_some() {
+                        println!("Settings stay the same while recording");
+                    } else {
+                        reload_settings(&mut settings, &mut simulation);
+                    }
+                }
+                glium::glutin::event::WindowEvent::KeyboardInput {
+                    input:
+                        glium::glutin::event::KeyboardInput {
+                            state: glium::glutin::event::ElementState::Pressed,
+                            virtual_keycode: Some(CAMERA_MODE_KEY),
+                            ..
+                        },
+                    ..
+                } => {
+                    camera_mode = camera_mode.next();
+                    third_person.reset_orbit();
+                }
+                glium::glutin::event::WindowEvent::MouseInput { .. } => {
+                    if let Some(input) = InputEvent::from_window_event(event) {
+                        process_input(&mut simulation, &mut recorder, input);
+                    }
+                }
+                _ => (),
             },
+            glium::glutin::event::Event::DeviceEvent {
+                event: glium::glutin::event::DeviceEvent::MouseMotion { delta },
+                ..
+            } if orbiting && camera_mode == CameraMode::ThirdPerson => {
+                // Only the view moves, so there's nothing for the simulation or a recording
+                let look_speed = simulation.player.lookaround_speed * MOUSE_LOOK_RATE;
+                third_person.orbit(-delta.0 as f32 * look_speed, -delta.1 as f32 * look_speed);
+            }
             glium::glutin::event::Event::DeviceEvent { event, .. } => {
-                keyboard_input.process_event(event);
+                if let Some(input) = InputEvent::from_device_event(event) {
+                    process_input(&mut simulation, &mut recorder, input);
+                }
             }
-            glium::glutin::event::Event::NewEvents(cause) => match cause {
-                glium::glutin::event::StartCause::ResumeTimeReached { .. } => (),
-                glium::glutin::event::StartCause::Init => (),
-                _ => return,
-            },
-
             glium::glutin::event::Event::MainEventsCleared => {
-                let mut target = display.draw();
-                target.clear_color(0.8, 0.85, 1.0, 1.0);
-                target.clear_depth(1.0);
-
-                chunk_mesh.draw(
-                    &mut target,
-                    &shader_program,
-                    atlas.get_texture(),
-                    &player.camera,
-                    player.transform,
-                );
-
-                for mesh in &chunk_meshes {
-                    mesh.draw(
-                        &mut target,
-                        &shader_program,
-                        atlas.get_texture(),
-                        &player.camera,
-                        player.transform,
-                    );
+                // Events keep the loop busy, frames are only drawn as often as the fps target
+                let now = Instant::now();
+                if now < next_frame {
+                    *control_flow = glium::glutin::event_loop::ControlFlow::WaitUntil(next_frame);
+                    return;
+                }
+                let frame_time = Duration::from_secs_f32(1.0 / settings.graphics.fps_target as f32);
+                next_frame = (next_frame + frame_time).max(now);
+                *control_flow = glium::glutin::event_loop::ControlFlow::WaitUntil(next_frame);
+
+                renderer.shaders.reload_changed(&display);
+
+                if let Some(gamepads) = &mut gamepads {
+                    for event in gamepads.poll() {
+                        process_input(
+                            &mut simulation,
+                            &mut recorder,
+                            InputEvent::Gamepad { event },
+                        );
+                    }
                 }
 
+                let elapsed = (now - last_frame).as_secs_f32();
+                last_frame = now;
+                for _ in 0..clock.advance(elapsed) {
+                    simulation.tick();
+                    if let Some(recorder) = &mut recorder {
+                        recorder.record_tick(&simulation);
+                    }
+                }
+                let dirty_chunks = simulation.take_dirty_chunks();
+                renderer.update_chunks(&display, &simulation.world, &dirty_chunks);
+
+                let player_transform = simulation.render_transform(clock.alpha());
+                let player_camera = &simulation.player.camera;
+                let (camera, camera_transform): (&dyn Camera, _) = match camera_mode {
+                    CameraMode::FirstPerson => (player_camera, player_transform),
+                    CameraMode::ThirdPerson => (
+                        player_camera,
+                        third_person.place(player_transform, player_camera, &simulation.world),
+                    ),
+                    CameraMode::Map => (&map.camera, map.place(player_transform)),
+                };
+                let mut target = display.draw();
+                renderer.draw(&mut target, camera, camera_transform, &simulation.time);
                 target.finish().unwrap();
+
+                // Keep the cursor in the middle so it never leaves the window
+                let size = display.gl_window().window().inner_size();
+                display
+                    .gl_window()
+                    .window()
+                    .set_cursor_position(glium::glutin::dpi::PhysicalPosition::new(
+                        size.width / 2,
+                        size.height / 2,
+                    ))
+                    .unwrap();
             }
             _ => (),
         }
-
-        player.process_event(&ev, delta);
-        player.update(&keyboard_input, delta);
-        display
-            .gl_window()
-            .window()
-            .set_cursor_position(glium::glutin::dpi::LogicalPosition::new(400.0, 400.0))
-            .unwrap();
-
-        delta = (std::time::Instant::now() - frame_start).as_secs_f32();
     });
 }
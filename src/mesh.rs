@@ -1,7 +1,10 @@
 use crate::transform;
 use crate::transform::Transform;
-use crate::vertex::Vertex;
-use crate::{camera::Camera, texture_atlas::TextureAtlas};
+use crate::vertex::{MeshVertex, Vertex};
+use crate::{
+    block::RenderLayer, camera::Camera, frustum::Aabb, sky::SceneLighting,
+    texture_atlas::TextureAtlas,
+};
 use glium::{
     backend::Facade,
     program::Program,
@@ -10,100 +13,371 @@ use glium::{
     IndexBuffer, Surface, VertexBuffer,
 };
 
-pub struct Mesh {
+/// Translucent meshes are blended over what's behind them and don't hide each other,
+/// so they have to be drawn last and back to front.
+fn draw_parameters(layer: RenderLayer) -> glium::DrawParameters<'static> {
+    glium::DrawParameters {
+        depth: glium::Depth {
+            test: glium::draw_parameters::DepthTest::IfLess,
+            write: layer != RenderLayer::Translucent,
+            ..Default::default()
+        },
+        blend: if layer == RenderLayer::Translucent {
+            glium::Blend::alpha_blending()
+        } else {
+            Default::default()
+        },
+        backface_culling: glium::draw_parameters::BackfaceCullingMode::CullCounterClockwise,
+        ..Default::default()
+    }
+}
+
+/// Most quads a mesh can have and still use the shared `QuadIndexBuffer`,
+/// as many as u16 indices can reach.
+pub const MAX_SHARED_QUADS: usize = (u16::MAX as usize + 1) / 4;
+
+/// Order the vertices of a quad added with `add_quad` are drawn in.
+const QUAD_INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+/// Meshes made of nothing but quads from `add_quad` all have the same indices,
+/// so they share one buffer instead of each uploading their own.
+pub struct QuadIndexBuffer {
+    buffer: IndexBuffer<u16>,
+}
+
+impl QuadIndexBuffer {
+    pub fn new(facade: &dyn Facade) -> QuadIndexBuffer {
+        let indices: Vec<u16> = (0..MAX_SHARED_QUADS as u32)
+            .flat_map(|quad| QUAD_INDICES.map(|i| (quad * 4 + i) as u16))
+            .collect();
+        QuadIndexBuffer {
+            buffer: IndexBuffer::new(facade, glium::index::PrimitiveType::TrianglesList, &indices)
+                .expect("Failed to create quad index buffer"),
+        }
+    }
+}
+
+/// Smallest GPU buffer a mesh allocates, in vertices or indices.
+const MIN_BUFFER_CAPACITY: usize = 64;
+
+/// Whether a buffer that holds `capacity` items can be reused for `len` of them.
+/// Buffers way bigger than needed are let go, so a mesh that shrank doesn't keep hogging memory.
+fn buffer_fits(capacity: usize, len: usize) -> bool {
+    capacity >= len && capacity <= len.max(MIN_BUFFER_CAPACITY) * 4
+}
+
+/// Capacity of a new buffer for `len` items, with room to grow.
+fn buffer_capacity(len: usize) -> usize {
+    len.max(MIN_BUFFER_CAPACITY).next_power_of_two()
+}
+
+/// Writes `data` to the start of `buffer`, reallocating it first if it doesn't fit.
+fn upload_indices<T: glium::index::Index>(
+    facade: &dyn Facade,
+    buffer: Option<IndexBuffer<T>>,
+    data: &[T],
+) -> IndexBuffer<T> {
+    let mut buffer = match buffer {
+        Some(buffer) if buffer_fits(buffer.len(), data.len()) => buffer,
+        _ => IndexBuffer::empty_dynamic(
+            facade,
+            glium::index::PrimitiveType::TrianglesList,
+            buffer_capacity(data.len()),
+        )
+        .expect("Failed to create ibo"),
+    };
+    if !data.is_empty() {
+        buffer.slice_mut(0..data.len()).unwrap().write(data);
+    }
+    buffer
+}
+
+/// Where a built mesh gets its indices from. Own buffers can be bigger than needed,
+/// only the first so many indices are used.
+enum Indices {
+    /// The first this many quads of the `QuadIndexBuffer`.
+    SharedQuads(usize),
+    U16(IndexBuffer<u16>, usize),
+    U32(IndexBuffer<u32>, usize),
+}
+
+/// Which of the `Indices` a mesh gets when it's built.
+#[derive(Debug, PartialEq, Eq)]
+enum IndexKind {
+    SharedQuads,
+    U16,
+    U32,
+}
+
+/// Everything a mesh needs to be drawn besides itself.
+pub struct DrawContext<'a> {
+    pub program: &'a Program,
+    pub atlas: &'a TextureAtlas,
+    pub camera: &'a dyn Camera,
+    pub camera_transform: Transform,
+    pub lighting: &'a SceneLighting,
+    pub quad_indices: &'a QuadIndexBuffer,
+}
+
+pub struct Mesh<V: MeshVertex = Vertex> {
     pub transform: Transform,
-    pub vertices: Vec<Vertex>,
+    pub vertices: Vec<V>,
     pub indices: Vec<u32>,
+    /// Pass the mesh is drawn in.
+    pub layer: RenderLayer,
+    /// Box around the vertices as of the last `build`, before `transform`.
+    bounds: Option<Aabb>,
 
-    vbo: Option<VertexBuffer<Vertex>>,
-    vao: Option<IndexBuffer<u32>>,
+    /// Buffers stay around between builds and are only reallocated when the mesh
+    /// outgrows them, so remeshing mostly just writes into existing GPU memory.
+    vbo: Option<VertexBuffer<V>>,
+    vertex_count: usize,
+    ibo: Option<Indices>,
 }
 
-impl Mesh {
-    pub fn empty() -> Mesh {
+impl<V: MeshVertex> Mesh<V> {
+    pub fn empty() -> Mesh<V> {
         Mesh {
             transform: Transform::zero(),
             vertices: Vec::new(),
             indices: Vec::new(),
+            layer: RenderLayer::Opaque,
+            bounds: None,
 
             vbo: None,
-            vao: None,
+            vertex_count: 0,
+            ibo: None,
+        }
+    }
+
+    /// Makes room for `quads` more quads.
+    pub fn reserve_quads(&mut self, quads: usize) {
+        self.vertices.reserve(quads * 4);
+        self.indices.reserve(quads * 6);
+    }
+
+    /// Whether the indices are exactly the ones `add_quad` makes, so the shared
+    /// `QuadIndexBuffer` can stand in for them.
+    fn is_quad_list(&self) -> bool {
+        self.vertices.len().is_multiple_of(4)
+            && self.indices.len() == self.vertices.len() / 4 * 6
+            && self.indices.chunks(6).enumerate().all(|(quad, indices)| {
+                indices
+                    .iter()
+                    .zip(QUAD_INDICES)
+                    .all(|(index, i)| *index == quad as u32 * 4 + i)
+            })
+    }
+
+    /// The shared quad indices if they fit, otherwise the smallest index type that reaches
+    /// every vertex.
+    fn index_kind(&self) -> IndexKind {
+        if self.is_quad_list() && self.vertices.len() / 4 <= MAX_SHARED_QUADS {
+            IndexKind::SharedQuads
+        } else if self.vertices.len() <= u16::MAX as usize + 1 {
+            IndexKind::U16
+        } else {
+            IndexKind::U32
         }
     }
 
     pub fn build(&mut self, facade: &dyn Facade) {
-        self.vbo = Some(VertexBuffer::new(facade, &self.vertices).expect("Failed to create vbo"));
-        self.vao = Some(
-            IndexBuffer::new(
-                facade,
-                glium::index::PrimitiveType::TrianglesList,
-                &self.indices,
-            )
-            .expect("Failed to create vao"),
-        );
+        self.bounds = Aabb::from_points(self.vertices.iter().map(|v| v.position().into()));
+        let len = self.vertices.len();
+        let mut vbo = match self.vbo.take() {
+            Some(vbo) if buffer_fits(vbo.len(), len) => vbo,
+            _ => VertexBuffer::empty_dynamic(facade, buffer_capacity(len))
+                .expect("Failed to create vbo"),
+        };
+        if len > 0 {
+            vbo.slice_mut(0..len).unwrap().write(&self.vertices);
+        }
+        self.vbo = Some(vbo);
+        self.vertex_count = len;
+
+        let quads = len / 4;
+        let (old_u16, old_u32) = match self.ibo.take() {
+            Some(Indices::U16(buffer, _)) => (Some(buffer), None),
+            Some(Indices::U32(buffer, _)) => (None, Some(buffer)),
+            _ => (None, None),
+        };
+        self.ibo = Some(match self.index_kind() {
+            IndexKind::SharedQuads => Indices::SharedQuads(quads),
+            IndexKind::U16 => {
+                let indices: Vec<u16> = self.indices.iter().map(|i| *i as u16).collect();
+                Indices::U16(upload_indices(facade, old_u16, &indices), indices.len())
+            }
+            IndexKind::U32 => Indices::U32(
+                upload_indices(facade, old_u32, &self.indices),
+                self.indices.len(),
+            ),
+        });
+    }
+
+    pub fn is_built(&self) -> bool {
+        self.vbo.is_some()
     }
 
-    pub fn draw<S: Surface>(
-        &self,
-        surface: &mut S,
-        shader_program: &Program,
-        texture: &glium::texture::SrgbTexture2d,
-        camera: &dyn Camera,
-        camera_transform: Transform,
-    ) {
-        if self.vbo.is_none() && self.vao.is_none() {
+    /// Box around the built mesh where it sits in the world, `None` if it has no vertices.
+    pub fn world_bounds(&self) -> Option<Aabb> {
+        self.bounds
+            .map(|bounds| bounds.transformed(&self.transform.to_matrix()))
+    }
+
+    pub fn draw<S: Surface>(&self, surface: &mut S, context: &DrawContext) {
+        let (Some(vbo), Some(ibo)) = (&self.vbo, &self.ibo) else {
+            return;
+        };
+        if self.vertex_count == 0 {
             return;
         }
+        let indices: glium::index::IndicesSource = match ibo {
+            Indices::SharedQuads(quads) => context
+                .quad_indices
+                .buffer
+                .slice(0..quads * 6)
+                .unwrap()
+                .into(),
+            Indices::U16(buffer, len) => buffer.slice(0..*len).unwrap().into(),
+            Indices::U32(buffer, len) => buffer.slice(0..*len).unwrap().into(),
+        };
+        let lighting = context.lighting;
+
         surface
             .draw(
-                self.vbo.as_ref().unwrap(),
-                self.vao.as_ref().unwrap(),
-                shader_program,
+                vbo.slice(0..self.vertex_count).unwrap(),
+                indices,
+                context.program,
                 &uniform! {
                     transform : transform::mat2array(self.transform.to_matrix()),
-                    view : transform::mat2array(camera.view(camera_transform)),
-                    projection : transform::mat2array(camera.projection()),
-                    albedo : glium::uniforms::Sampler(texture, glium::uniforms::SamplerBehavior {
+                    view : transform::mat2array(context.camera.view(context.camera_transform)),
+                    projection : transform::mat2array(context.camera.projection()),
+                    atlas_tiles : context.atlas.tiles().map(|t| t as f32),
+                    albedo : glium::uniforms::Sampler(context.atlas.get_texture(), glium::uniforms::SamplerBehavior {
                         minify_filter : MinifySamplerFilter::Nearest,
                         magnify_filter : MagnifySamplerFilter::Nearest,
                         ..Default::default()
-                    })
-                },
-                &glium::draw_parameters::DrawParameters {
-                    depth: glium::Depth {
-                        test: glium::draw_parameters::DepthTest::IfLess,
-                        write: true,
-                        ..Default::default()
-                    },
-                    backface_culling:
-                        glium::draw_parameters::BackfaceCullingMode::CullCounterClockwise,
-                    ..Default::default()
+                    }),
+                    light_direction : lighting.light_direction,
+                    light_color : lighting.light_color,
+                    sky_brightness : lighting.sky_brightness,
+                    fog_color : lighting.fog_color,
+                    fog_start : lighting.fog_start,
+                    fog_end : lighting.fog_end,
+                    alpha_cutoff : if self.layer == RenderLayer::Cutout { 0.5f32 } else { 0.0f32 },
+                    blend_alpha : self.layer == RenderLayer::Translucent,
                 },
+                &draw_parameters(self.layer),
             )
             .expect("Failed to draw mesh");
     }
 
-    pub fn add_quad(&mut self, quad: [Vertex; 4]) {
-        for vertex in quad {
-            self.vertices.push(vertex);
+    pub fn add_quad(&mut self, quad: [V; 4]) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&quad);
+        self.indices.extend(QUAD_INDICES.map(|i| base + i));
+    }
+
+    /// Same as `add_quad`, but splits the quad along the diagonal between vertex 1 and 3.
+    /// The vertices are rotated instead of the indices, so the mesh can still share
+    /// the `QuadIndexBuffer`.
+    pub fn add_quad_flipped(&mut self, quad: [V; 4]) {
+        let [v0, v1, v2, v3] = quad;
+        self.add_quad([v1, v2, v3, v0]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra_glm as glm;
+
+    use super::*;
+
+    // Corners of a square facing +y, counter-clockwise seen from above
+    fn square() -> [Vertex; 4] {
+        [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]].map(|[x, z]| Vertex {
+            position: [x, 0.0, z],
+            ..Default::default()
+        })
+    }
+
+    fn mesh_of_quads(quads: usize) -> Mesh {
+        let mut mesh = Mesh::empty();
+        mesh.reserve_quads(quads);
+        for _ in 0..quads {
+            mesh.add_quad(square());
         }
+        mesh
+    }
+
+    // Triangle normals, which way round the triangles are wound
+    fn triangle_normals(mesh: &Mesh) -> Vec<glm::Vec3> {
+        mesh.indices
+            .chunks(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2]
+                    .map(|i| glm::Vec3::from(mesh.vertices[triangle[i] as usize].position));
+                glm::normalize(&glm::cross(&(b - a), &(c - a)))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn quads_make_a_quad_list() {
+        assert!(Mesh::<Vertex>::empty().is_quad_list());
+        let mut mesh = mesh_of_quads(2);
+        assert!(mesh.is_quad_list());
+        mesh.add_quad_flipped(square());
+        assert!(mesh.is_quad_list());
 
-        let mut len = self
+        mesh.indices.swap(0, 1);
+        assert!(!mesh.is_quad_list());
+        let mut mesh = mesh_of_quads(1);
+        mesh.vertices.push(square()[0]);
+        assert!(!mesh.is_quad_list());
+    }
+
+    #[test]
+    fn flipped_quad_splits_along_the_other_diagonal() {
+        let mut mesh = Mesh::empty();
+        mesh.add_quad_flipped(square());
+        let corners = square().map(|v| v.position);
+        let triangles: Vec<Vec<usize>> = mesh
             .indices
-            .iter()
-            .copied()
-            .reduce(|max, i| if i > max { i } else { max })
-            .unwrap_or(0u32);
-        if len != 0 {
-            len += 1u32;
-        }
+            .chunks(3)
+            .map(|triangle| {
+                triangle
+                    .iter()
+                    .map(|i| {
+                        let position = mesh.vertices[*i as usize].position;
+                        corners.iter().position(|c| *c == position).unwrap()
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(triangles, [[1, 2, 3], [1, 3, 0]]);
 
-        self.indices.push(len + 0);
-        self.indices.push(len + 1);
-        self.indices.push(len + 2);
+        // Wound the same way as the unflipped quad, so backface culling treats them alike
+        let normals = triangle_normals(&mesh);
+        assert_eq!(normals, triangle_normals(&mesh_of_quads(1)));
+        assert_eq!(normals[0], normals[1]);
+    }
+
+    #[test]
+    fn big_meshes_fall_back_to_their_own_indices() {
+        assert_eq!(
+            mesh_of_quads(MAX_SHARED_QUADS).index_kind(),
+            IndexKind::SharedQuads
+        );
+        assert_eq!(
+            mesh_of_quads(MAX_SHARED_QUADS + 1).index_kind(),
+            IndexKind::U32
+        );
 
-        self.indices.push(len + 0);
-        self.indices.push(len + 2);
-        self.indices.push(len + 3);
+        let mut mesh = mesh_of_quads(MAX_SHARED_QUADS);
+        mesh.indices.swap(0, 1);
+        assert_eq!(mesh.index_kind(), IndexKind::U16);
+        mesh.add_quad(square());
+        assert_eq!(mesh.index_kind(), IndexKind::U32);
     }
 }
@@ -1,11 +1,27 @@
+use crate::actions::{Action, Actions};
 use crate::camera::PerspectiveCamera3D;
-use crate::keyboard::Keyboard;
 use crate::kinematic_body::KinematicBody;
+use crate::settings::Settings;
 use crate::transform::Transform;
 use crate::util::numbers::move_toward;
 
+/// Radians the camera turns per count of mouse motion, per unit of `lookaround_speed`.
+pub const MOUSE_LOOK_RATE: f32 = 1.0 / 60_000.0;
+
+/// Radians per second a stick pushed all the way turns the camera, per unit of
+/// `lookaround_speed`.
+pub const STICK_LOOK_RATE: f32 = 1.0 / 60.0;
+
+/// Furthest the camera tilts up or down, looking straight up or down would flip it over.
+pub const MAX_PITCH: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
 pub struct Player {
     pub transform: Transform,
+    /// Radians counterclockwise from -z seen from above, what `transform` faces comes from this
+    /// and `pitch`.
+    pub yaw: f32,
+    /// Radians up from the horizon, between `-MAX_PITCH` and `MAX_PITCH`.
+    pub pitch: f32,
     pub camera: PerspectiveCamera3D,
     pub walk_speed: f32,
     pub lookaround_speed: f32,
@@ -18,12 +34,15 @@ impl Player {
     pub fn new(
         walk_speed: f32,
         lookaround_speed: f32,
+        fov: f32,
         walk_acceleration: f32,
         walk_deceleration: f32,
     ) -> Player {
         Player {
             transform: Transform::zero(),
-            camera: PerspectiveCamera3D::new(16.0 / 9.0, 3.14 / 1.7, 0.01, 1024.0),
+            yaw: 0.0,
+            pitch: 0.0,
+            camera: PerspectiveCamera3D::new(16.0 / 9.0, fov, 0.01, 1024.0),
             walk_speed,
             lookaround_speed,
             kinematic_body: KinematicBody::new(80.0),
@@ -32,67 +51,42 @@ impl Player {
         }
     }
 
-    pub fn update(&mut self, input: &Keyboard, delta_time: f32) {
-        use glium::glutin::event::VirtualKeyCode;
-        self.kinematic_body.update(delta_time);
-
-        if input.is_key_pressed(VirtualKeyCode::W) {
-            self.kinematic_body.velocity.z = move_toward(
-                self.kinematic_body.velocity.z,
-                -self.walk_speed,
-                self.walk_acceleration * delta_time,
-            );
-        } else {
-            self.kinematic_body.velocity.z = move_toward(
-                self.kinematic_body.velocity.z,
-                0.0,
-                self.walk_deceleration * delta_time,
-            );
-        }
+    pub fn from_settings(settings: &Settings) -> Player {
+        Player::new(
+            settings.gameplay.walk_speed,
+            settings.controls.mouse_sensitivity,
+            settings.graphics.fov.to_radians(),
+            50.0,
+            10.0,
+        )
+    }
 
-        if input.is_key_pressed(VirtualKeyCode::S) {
-            self.kinematic_body.velocity.z = move_toward(
-                self.kinematic_body.velocity.z,
-                self.walk_speed,
-                self.walk_acceleration * delta_time,
-            );
-        } else {
-            self.kinematic_body.velocity.z = move_toward(
-                self.kinematic_body.velocity.z,
-                0.0,
-                self.walk_deceleration * delta_time,
-            );
-        }
+    /// Picks up whatever can change while playing.
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.walk_speed = settings.gameplay.walk_speed;
+        self.lookaround_speed = settings.controls.mouse_sensitivity;
+        self.camera.fov = settings.graphics.fov.to_radians();
+    }
 
-        if input.is_key_pressed(VirtualKeyCode::A) {
-            self.kinematic_body.velocity.x = move_toward(
-                self.kinematic_body.velocity.x,
-                -self.walk_speed,
-                self.walk_acceleration * delta_time,
-            );
-        } else {
-            self.kinematic_body.velocity.x = move_toward(
-                self.kinematic_body.velocity.x,
-                0.0,
-                self.walk_deceleration * delta_time,
-            );
-        }
+    pub fn update(&mut self, actions: &Actions, delta_time: f32) {
+        self.kinematic_body.update(delta_time);
 
-        if input.is_key_pressed(VirtualKeyCode::D) {
-            self.kinematic_body.velocity.x = move_toward(
-                self.kinematic_body.velocity.x,
-                self.walk_speed,
-                self.walk_acceleration * delta_time,
-            );
-        } else {
-            self.kinematic_body.velocity.x = move_toward(
-                self.kinematic_body.velocity.x,
-                0.0,
-                self.walk_deceleration * delta_time,
-            );
+        // Analog sticks walk slower the less they're pushed
+        let [right, forward] = actions.movement();
+        let target = [right * self.walk_speed, -forward * self.walk_speed];
+        for (velocity, target) in [
+            (&mut self.kinematic_body.velocity.x, target[0]),
+            (&mut self.kinematic_body.velocity.z, target[1]),
+        ] {
+            let rate = if target == 0.0 {
+                self.walk_deceleration
+            } else {
+                self.walk_acceleration
+            };
+            *velocity = move_toward(*velocity, target, rate * delta_time);
         }
 
-        if input.is_key_pressed(VirtualKeyCode::Space) {
+        if actions.is_active(Action::Jump) {
             self.kinematic_body.velocity.y = move_toward(
                 self.kinematic_body.velocity.y,
                 self.walk_speed,
@@ -105,7 +99,7 @@ impl Player {
                 self.walk_deceleration * delta_time,
             );
         }
-        if input.is_key_pressed(VirtualKeyCode::C) {
+        if actions.is_active(Action::Sneak) {
             self.kinematic_body.velocity.y = move_toward(
                 self.kinematic_body.velocity.y,
                 -self.walk_speed,
@@ -119,49 +113,48 @@ impl Player {
             );
         }
 
+        // Walking stays level whichever way the camera is tilted
         self.transform.position = self.transform.position
-            + self
-                .kinematic_body
-                .velocity
-                .rotated_y(self.transform.get_rotation().y + 90.0f32.to_radians())
-                * delta_time;
-    }
+            + self.kinematic_body.velocity.rotated_y(-self.yaw) * delta_time;
 
-    pub fn process_event(&mut self, ev: &glium::glutin::event::Event<()>, delta_time: f32) {
-        match ev {
-            glium::glutin::event::Event::DeviceEvent { event, .. } => match event {
-                glium::glutin::event::DeviceEvent::MouseMotion { delta } => {
-                    let d_x = delta.0 as f32;
-                    let d_y = delta.1 as f32;
-
-                    self.transform
-                        .rotate_y(d_x * self.lookaround_speed * delta_time);
-                    self.transform
-                        .rotate_x(-d_y * self.lookaround_speed * delta_time);
-                }
-
-                _ => (),
-            },
-            _ => (),
-        }
+        let [look_x, look_y] = actions.look();
+        let look_speed = self.lookaround_speed * STICK_LOOK_RATE * delta_time;
+        self.turn(-look_x * look_speed, look_y * look_speed);
     }
-    fn forward(&mut self, speed: f32) {
-        self.transform.position.z += speed * self.transform.get_rotation().y.sin();
-        self.transform.position.x += speed * self.transform.get_rotation().y.cos();
+
+    /// Mouse motion is a distance, not a rate, it turns the camera the same however long the
+    /// frame took.
+    pub fn mouse_look(&mut self, dx: f32, dy: f32) {
+        let look_speed = self.lookaround_speed * MOUSE_LOOK_RATE;
+        self.turn(-dx * look_speed, -dy * look_speed);
     }
 
-    fn backward(&mut self, speed: f32) {
-        self.transform.position.z -= speed * self.transform.get_rotation().y.sin();
-        self.transform.position.x -= speed * self.transform.get_rotation().y.cos();
+    /// Turns left by `yaw` and up by `pitch` radians.
+    pub fn turn(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = (self.yaw + yaw) % std::f32::consts::TAU;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+        self.transform.set_yaw_pitch(self.yaw, self.pitch);
     }
 
-    fn left(&mut self, speed: f32) {
-        self.transform.position.x += speed * self.transform.get_rotation().y.sin();
-        self.transform.position.z -= speed * self.transform.get_rotation().y.cos();
+    /// Unit vector the camera looks along.
+    pub fn look_direction(&self) -> [f32; 3] {
+        self.transform.forward().into()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pitch_stops_short_of_straight_up_and_down() {
+        let mut player = Player::new(1.0, 1.0, 1.0, 1.0, 1.0);
+        player.turn(0.0, 10.0);
+        assert_eq!(player.pitch, MAX_PITCH);
+        assert!(player.look_direction()[1] < 1.0);
 
-    fn right(&mut self, speed: f32) {
-        self.transform.position.x -= speed * self.transform.get_rotation().y.sin();
-        self.transform.position.z += speed * self.transform.get_rotation().y.cos();
+        player.turn(0.0, -20.0);
+        assert_eq!(player.pitch, -MAX_PITCH);
+        assert!(player.look_direction()[1] > -1.0);
     }
 }
@@ -0,0 +1,212 @@
+use std::collections::{HashMap, HashSet};
+
+use glium::{
+    backend::Facade,
+    framebuffer::{DepthRenderBuffer, SimpleFrameBuffer},
+    texture::{DepthFormat, SrgbTexture2d},
+    Surface,
+};
+use image::RgbaImage;
+use nalgebra_glm as glm;
+
+use crate::{
+    block::BlockId,
+    camera::Camera,
+    chunk::{ChunkMesh, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH},
+    frustum::{Aabb, Frustum},
+    mesh::{DrawContext, Mesh, QuadIndexBuffer},
+    shader::{ShaderManager, SHADER_DIRECTORY},
+    sky::{self, WorldTime},
+    texture_atlas::TextureAtlas,
+    transform::Transform,
+    vertex::ChunkVertex,
+    visibility,
+    world::World,
+};
+
+pub const ATLAS_PATH: &str = "res/textures/blocks.png";
+
+/// Blocks in the order of the columns of the atlas.
+pub const ATLAS_BLOCKS: [BlockId; 9] = [
+    BlockId::Dirt,
+    BlockId::Grass,
+    BlockId::Stone,
+    BlockId::Log,
+    BlockId::Leaves,
+    BlockId::Lamp,
+    BlockId::Sand,
+    BlockId::Water,
+    BlockId::Glass,
+];
+
+/// Draws the world, into a window or anything else that's a `Surface`.
+pub struct Renderer {
+    pub shaders: ShaderManager,
+    pub atlas: TextureAtlas,
+    /// Distance from the camera where everything disappears into the fog.
+    pub fog_distance: f32,
+    quad_indices: QuadIndexBuffer,
+    /// Empty chunks are kept too, cave culling has to see through them.
+    chunk_meshes: HashMap<[i64; 3], ChunkMesh>,
+}
+
+impl Renderer {
+    pub fn new(facade: &dyn Facade, fog_distance: f32) -> Result<Renderer, String> {
+        let mut shaders = ShaderManager::new(SHADER_DIRECTORY);
+        for name in ["mesh", "chunk", "sky"] {
+            shaders.load(facade, name)?;
+        }
+
+        Ok(Renderer {
+            shaders,
+            atlas: TextureAtlas::load(facade, ATLAS_PATH.to_owned()).with_blocks(6, &ATLAS_BLOCKS),
+            fog_distance,
+            quad_indices: QuadIndexBuffer::new(facade),
+            chunk_meshes: HashMap::new(),
+        })
+    }
+
+    /// Meshes every chunk of the world, replacing whatever was meshed before.
+    pub fn mesh_world(&mut self, facade: &dyn Facade, world: &World) {
+        self.chunk_meshes.clear();
+        for chunk in world.chunks() {
+            let mut chunk_mesh =
+                chunk.generate_mesh(&world.neighbors(chunk.position()), &self.atlas);
+            chunk_mesh.build(facade);
+            self.chunk_meshes.insert(chunk.position(), chunk_mesh);
+        }
+    }
+
+    /// Meshes the `chunks` of the world again after blocks in them changed.
+    pub fn update_chunks(
+        &mut self,
+        facade: &dyn Facade,
+        world: &World,
+        chunks: &HashSet<[i64; 3]>,
+    ) {
+        for position in chunks {
+            let Some(chunk) = world.get_chunk(*position) else {
+                continue;
+            };
+            let mut chunk_mesh = chunk.generate_mesh(&world.neighbors(*position), &self.atlas);
+            match self.chunk_meshes.get_mut(position) {
+                Some(old) => old.update(facade, chunk_mesh),
+                None => {
+                    chunk_mesh.build(facade);
+                    self.chunk_meshes.insert(*position, chunk_mesh);
+                }
+            }
+        }
+    }
+
+    pub fn draw<S: Surface>(
+        &self,
+        surface: &mut S,
+        camera: &dyn Camera,
+        camera_transform: Transform,
+        time: &WorldTime,
+    ) {
+        let lighting = time.lighting(self.fog_distance * 0.5, self.fog_distance);
+        let [r, g, b] = lighting.fog_color;
+        surface.clear_color_and_depth((r, g, b, 1.0), 1.0);
+
+        sky::draw_sky(
+            surface,
+            self.shaders.get("sky"),
+            camera,
+            camera_transform,
+            time,
+        );
+
+        let frustum = Frustum::from_camera(camera, camera_transform);
+        let chunk_size = glm::vec3(CHUNK_WIDTH as f32, CHUNK_HEIGHT as f32, CHUNK_DEPTH as f32);
+        // Blocks are centered on whole numbers, a chunk starts half a block before its origin
+        let half_block = glm::vec3(0.5, 0.5, 0.5);
+        let position = camera_transform.position;
+        let (camera_chunk, _) = World::split_point([
+            position.x.round() as i64,
+            position.y.round() as i64,
+            position.z.round() as i64,
+        ]);
+        let visible = visibility::visible_chunks(
+            camera_chunk,
+            |chunk| self.chunk_meshes.get(&chunk).map(|m| m.visibility),
+            |chunk| {
+                let min = glm::vec3(chunk[0] as f32, chunk[1] as f32, chunk[2] as f32)
+                    .component_mul(&chunk_size)
+                    - half_block;
+                frustum.intersects(&Aabb::new(min, min + chunk_size))
+            },
+        );
+        let drawn: Vec<&ChunkMesh> = self
+            .chunk_meshes
+            .iter()
+            .filter(|(chunk, _)| visible.as_ref().is_none_or(|v| v.contains(*chunk)))
+            .map(|(_, mesh)| mesh)
+            .collect();
+
+        // Translucent chunks go last, the farthest first so the closer ones blend over them
+        let camera_position: glm::Vec3 = position.into();
+        let to_center = chunk_size / 2.0 - half_block;
+        let mut translucent: Vec<&Mesh<ChunkVertex>> =
+            drawn.iter().map(|m| &m.translucent).collect();
+        translucent.sort_by(|a, b| {
+            let distance = |mesh: &Mesh<ChunkVertex>| {
+                let origin: glm::Vec3 = mesh.transform.position.into();
+                glm::distance2(&(origin + to_center), &camera_position)
+            };
+            distance(b).total_cmp(&distance(a))
+        });
+
+        let passes = [
+            drawn.iter().map(|m| &m.opaque).collect(),
+            drawn.iter().map(|m| &m.cutout).collect(),
+            translucent,
+        ];
+        let context = DrawContext {
+            program: self.shaders.get("chunk"),
+            atlas: &self.atlas,
+            camera,
+            camera_transform,
+            lighting: &lighting,
+            quad_indices: &self.quad_indices,
+        };
+        for meshes in passes {
+            for mesh in meshes {
+                if !mesh
+                    .world_bounds()
+                    .is_some_and(|bounds| frustum.intersects(&bounds))
+                {
+                    continue;
+                }
+                mesh.draw(surface, &context);
+            }
+        }
+    }
+
+    /// Draws a frame into a texture instead of a window and reads it back.
+    pub fn render_to_image(
+        &self,
+        facade: &dyn Facade,
+        dimensions: (u32, u32),
+        camera: &dyn Camera,
+        camera_transform: Transform,
+        time: &WorldTime,
+    ) -> RgbaImage {
+        let (width, height) = dimensions;
+        let color = SrgbTexture2d::empty(facade, width, height)
+            .expect("Failed to create offscreen color texture");
+        let depth = DepthRenderBuffer::new(facade, DepthFormat::I24, width, height)
+            .expect("Failed to create offscreen depth buffer");
+        let mut target = SimpleFrameBuffer::with_depth_buffer(facade, &color, &depth)
+            .expect("Failed to create offscreen framebuffer");
+
+        self.draw(&mut target, camera, camera_transform, time);
+
+        let pixels: glium::texture::RawImage2d<u8> = color.read();
+        let image = RgbaImage::from_raw(width, height, pixels.data.into_owned())
+            .expect("Offscreen framebuffer has the wrong size");
+        // OpenGL starts at the bottom row, images at the top one
+        image::imageops::flip_vertical(&image)
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::Bindings,
+    settings::Settings,
+    simulation::{BlockEdit, InputEvent, Simulation, TICK_RATE},
+    world::WorldMetadata,
+};
+
+/// Ticks between the positions a recording keeps to check replays against.
+pub const CHECKPOINT_INTERVAL: u64 = TICK_RATE as u64;
+
+/// How far a replayed position may be from the recorded one, `sin` and `cos` round a little
+/// differently between platforms.
+pub const POSITION_TOLERANCE: f32 = 1e-3;
+
+/// An event and the tick it came in before.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub tick: u64,
+    #[serde(flatten)]
+    pub event: InputEvent,
+}
+
+/// Where the player was after a tick.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub tick: u64,
+    pub position: [f32; 3],
+}
+
+/// A session from the start, with everything needed to play it again the same way and what
+/// came out of it the first time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub world: WorldMetadata,
+    pub settings: Settings,
+    pub bindings: Bindings,
+    /// Ticks the session ran for.
+    pub ticks: u64,
+    #[serde(rename = "event", default)]
+    pub events: Vec<RecordedEvent>,
+    #[serde(rename = "checkpoint", default)]
+    pub checkpoints: Vec<Checkpoint>,
+    #[serde(rename = "edit", default)]
+    pub edits: Vec<BlockEdit>,
+}
+
+impl Recording {
+    pub fn load(path: &str) -> Result<Recording, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read recording {}: {}", path, e))?;
+        toml::from_str(&contents).map_err(|e| format!("Failed to parse recording {}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize recording: {}", e))?;
+        if let Some(dir) = std::path::Path::new(path).parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        std::fs::write(path, contents)
+            .map_err(|e| format!("Failed to write recording {}: {}", path, e))
+    }
+
+    /// Plays the session again in a fresh simulation, calling `on_tick` after every tick.
+    pub fn replay(&self, mut on_tick: impl FnMut(&Simulation)) -> Simulation {
+        let mut simulation = Simulation::new(&self.world, &self.settings, self.bindings.clone());
+        let mut events = self.events.iter().peekable();
+        while simulation.ticks < self.ticks {
+            while let Some(recorded) = events.next_if(|e| e.tick <= simulation.ticks) {
+                simulation.process_event(&recorded.event);
+            }
+            simulation.tick();
+            on_tick(&simulation);
+        }
+        simulation
+    }
+
+    /// Replays the session and checks that the player passes every checkpoint and edits the
+    /// same blocks as the first time.
+    pub fn verify(&self) -> Result<(), String> {
+        let wanted: HashMap<u64, [f32; 3]> = self
+            .checkpoints
+            .iter()
+            .map(|checkpoint| (checkpoint.tick, checkpoint.position))
+            .collect();
+        let mut positions = HashMap::new();
+        let simulation = self.replay(|simulation| {
+            if wanted.contains_key(&simulation.ticks) {
+                positions.insert(simulation.ticks, player_position(simulation));
+            }
+        });
+
+        for checkpoint in &self.checkpoints {
+            let Some(position) = positions.get(&checkpoint.tick) else {
+                return Err(format!("Replay never reached tick {}", checkpoint.tick));
+            };
+            if (0..3).any(|i| (position[i] - checkpoint.position[i]).abs() > POSITION_TOLERANCE) {
+                return Err(format!(
+                    "Player was at {:?} after tick {}, recorded at {:?}",
+                    position, checkpoint.tick, checkpoint.position
+                ));
+            }
+        }
+        if simulation.edits != self.edits {
+            return Err(format!(
+                "Replay edited {:?}, recorded {:?}",
+                simulation.edits, self.edits
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn player_position(simulation: &Simulation) -> [f32; 3] {
+    let position = simulation.player.transform.position;
+    [position.x, position.y, position.z]
+}
+
+/// Writes down a session while it's played. The simulation it records has to start out fresh
+/// from `Simulation::new` with the same metadata, settings and bindings.
+pub struct Recorder {
+    recording: Recording,
+}
+
+impl Recorder {
+    pub fn new(world: &WorldMetadata, settings: &Settings, bindings: &Bindings) -> Recorder {
+        Recorder {
+            recording: Recording {
+                world: world.clone(),
+                settings: settings.clone(),
+                bindings: bindings.clone(),
+                ticks: 0,
+                events: Vec::new(),
+                checkpoints: Vec::new(),
+                edits: Vec::new(),
+            },
+        }
+    }
+
+    /// Call right before handing `event` to `simulation`.
+    pub fn record_event(&mut self, simulation: &Simulation, event: InputEvent) {
+        self.recording.events.push(RecordedEvent {
+            tick: simulation.ticks,
+            event,
+        });
+    }
+
+    /// Call after every tick.
+    pub fn record_tick(&mut self, simulation: &Simulation) {
+        if simulation.ticks.is_multiple_of(CHECKPOINT_INTERVAL) {
+            self.recording.checkpoints.push(Checkpoint {
+                tick: simulation.ticks,
+                position: player_position(simulation),
+            });
+        }
+    }
+
+    pub fn finish(mut self, simulation: &Simulation) -> Recording {
+        self.recording.ticks = simulation.ticks;
+        self.recording.edits = simulation.edits.clone();
+        if !simulation.ticks.is_multiple_of(CHECKPOINT_INTERVAL) {
+            self.recording.checkpoints.push(Checkpoint {
+                tick: simulation.ticks,
+                position: player_position(simulation),
+            });
+        }
+        self.recording
+    }
+}
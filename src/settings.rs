@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+
+pub const SETTINGS_PATH: &str = "settings.toml";
+
+/// Options the player can change, kept in `settings.toml` next to the game.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub graphics: GraphicsSettings,
+    pub controls: ControlSettings,
+    pub gameplay: GameplaySettings,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    /// Vertical field of view in degrees.
+    pub fov: f32,
+    /// Chunks loaded along each horizontal axis, only read at startup.
+    pub render_distance: u32,
+    /// Only read at startup.
+    pub vsync: bool,
+    pub fps_target: u32,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings {
+            fov: 106.0,
+            render_distance: 1,
+            vsync: true,
+            fps_target: 120,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlSettings {
+    pub mouse_sensitivity: f32,
+}
+
+impl Default for ControlSettings {
+    fn default() -> Self {
+        ControlSettings {
+            mouse_sensitivity: 180.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameplaySettings {
+    /// Blocks per second.
+    pub walk_speed: f32,
+}
+
+impl Default for GameplaySettings {
+    fn default() -> Self {
+        GameplaySettings { walk_speed: 8.0 }
+    }
+}
+
+impl Settings {
+    /// Anything left out of the file falls back to the default.
+    pub fn load(path: &str) -> Result<Settings, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read settings {}: {}", path, e))?;
+        let settings: Settings = toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse settings {}: {}", path, e))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Loads the settings, or writes the defaults out on the first run so there's a file to edit.
+    pub fn load_or_create(path: &str) -> Result<Settings, String> {
+        if std::path::Path::new(path).exists() {
+            return Settings::load(path);
+        }
+        let settings = Settings::default();
+        settings.save(path)?;
+        Ok(settings)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        std::fs::write(path, contents)
+            .map_err(|e| format!("Failed to write settings {}: {}", path, e))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(30.0..=150.0).contains(&self.graphics.fov) {
+            return Err("fov must be between 30 and 150 degrees".to_owned());
+        }
+        if !(1..=32).contains(&self.graphics.render_distance) {
+            return Err("render_distance must be between 1 and 32 chunks".to_owned());
+        }
+        if self.graphics.fps_target == 0 {
+            return Err("fps_target must be positive".to_owned());
+        }
+        if self.controls.mouse_sensitivity <= 0.0 {
+            return Err("mouse_sensitivity must be positive".to_owned());
+        }
+        if self.gameplay.walk_speed <= 0.0 {
+            return Err("walk_speed must be positive".to_owned());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The error `change` gets from `validate`, starting from the defaults
+    fn error(change: impl FnOnce(&mut Settings)) -> String {
+        let mut settings = Settings::default();
+        change(&mut settings);
+        settings.validate().unwrap_err()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert_eq!(Settings::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn out_of_range_values_name_their_field() {
+        assert!(error(|s| s.graphics.fov = 29.0).starts_with("fov "));
+        assert!(error(|s| s.graphics.fov = 151.0).starts_with("fov "));
+        assert!(error(|s| s.graphics.render_distance = 0).starts_with("render_distance "));
+        assert!(error(|s| s.graphics.render_distance = 33).starts_with("render_distance "));
+        assert!(error(|s| s.graphics.fps_target = 0).starts_with("fps_target "));
+        assert!(error(|s| s.controls.mouse_sensitivity = 0.0).starts_with("mouse_sensitivity "));
+        assert!(error(|s| s.gameplay.walk_speed = -1.0).starts_with("walk_speed "));
+    }
+
+    #[test]
+    fn range_ends_are_valid() {
+        let mut settings = Settings::default();
+        settings.graphics.fov = 150.0;
+        settings.graphics.render_distance = 32;
+        assert_eq!(settings.validate(), Ok(()));
+        settings.graphics.fov = 30.0;
+        settings.graphics.render_distance = 1;
+        assert_eq!(settings.validate(), Ok(()));
+    }
+}
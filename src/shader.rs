@@ -0,0 +1,301 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use glium::{backend::Facade, program::ProgramCreationError, Program};
+
+pub const SHADER_DIRECTORY: &str = "res/shaders";
+
+/// How often `reload_changed` looks at the files on disk.
+const RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Shader code with every `#include` pasted in, remembering where each line came from.
+struct Source {
+    code: String,
+    lines: Vec<(PathBuf, usize)>,
+}
+
+impl Source {
+    fn load(path: &Path, files: &mut Vec<PathBuf>) -> Result<Source, String> {
+        let mut source = Source {
+            code: String::new(),
+            lines: Vec::new(),
+        };
+        source.append(path, files, &mut Vec::new())?;
+        Ok(source)
+    }
+
+    fn append(
+        &mut self,
+        path: &Path,
+        files: &mut Vec<PathBuf>,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), String> {
+        if stack.iter().any(|p| p == path) {
+            return Err(format!("{} includes itself", path.display()));
+        }
+        if !files.iter().any(|p| p == path) {
+            files.push(path.to_owned());
+        }
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        stack.push(path.to_owned());
+        for (i, line) in contents.lines().enumerate() {
+            if let Some(include) = line.trim_start().strip_prefix("#include") {
+                let name = include.trim().trim_matches('"');
+                if name.is_empty() {
+                    return Err(format!("{}:{}: empty #include", path.display(), i + 1));
+                }
+                // Relative to the file doing the including
+                let included = path.parent().unwrap_or(Path::new("")).join(name);
+                self.append(&included, files, stack)?;
+            } else {
+                self.code.push_str(line);
+                self.code.push('\n');
+                self.lines.push((path.to_owned(), i + 1));
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+
+    /// Line number a driver log line points at. Drivers disagree on the format,
+    /// this knows `0:12(5):` (Mesa), `0(12) :` (Nvidia) and `ERROR: 0:12:` (AMD, Intel).
+    fn log_line_number(line: &str) -> Option<usize> {
+        let line = line.trim_start();
+        let line = line.strip_prefix("ERROR:").unwrap_or(line).trim_start();
+        let line = line
+            .strip_prefix("0:")
+            .or_else(|| line.strip_prefix("0("))?;
+        let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+        line[..digits].parse().ok()
+    }
+
+    /// Prefixes the lines of a compile log with the file and line they are about.
+    fn annotate_log(&self, log: &str) -> String {
+        log.lines()
+            .map(|line| {
+                match Source::log_line_number(line).and_then(|n| self.lines.get(n.wrapping_sub(1)))
+                {
+                    Some((path, number)) => {
+                        format!("{}:{}: {}", path.display(), number, line.trim())
+                    }
+                    None => line.to_owned(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+struct ShaderProgram {
+    program: Program,
+    /// Every file the program was built from, with its modification time back then.
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Programs loaded from `<root>/<name>/vertex.glsl` and `fragment.glsl`.
+pub struct ShaderManager {
+    root: PathBuf,
+    programs: HashMap<String, ShaderProgram>,
+    last_check: Instant,
+}
+
+impl ShaderManager {
+    pub fn new(root: impl Into<PathBuf>) -> ShaderManager {
+        ShaderManager {
+            root: root.into(),
+            programs: HashMap::new(),
+            last_check: Instant::now(),
+        }
+    }
+
+    fn compile(
+        &self,
+        facade: &dyn Facade,
+        name: &str,
+        files: &mut Vec<PathBuf>,
+    ) -> Result<Program, String> {
+        let directory = self.root.join(name);
+        let vertex = Source::load(&directory.join("vertex.glsl"), files)?;
+        let fragment = Source::load(&directory.join("fragment.glsl"), files)?;
+
+        Program::from_source(facade, &vertex.code, &fragment.code, None).map_err(|e| match &e {
+            ProgramCreationError::CompilationError(log, shader_type) => {
+                let source = match shader_type {
+                    glium::program::ShaderType::Vertex => &vertex,
+                    _ => &fragment,
+                };
+                format!("{} of {}:\n{}", e, name, source.annotate_log(log))
+            }
+            ProgramCreationError::LinkingError(log) => format!("{} of {}:\n{}", e, name, log),
+            _ => format!("Failed to create shader {}: {}", name, e),
+        })
+    }
+
+    /// Compiles the program `name`, replacing it if it was already loaded.
+    pub fn load(&mut self, facade: &dyn Facade, name: &str) -> Result<(), String> {
+        let mut files = Vec::new();
+        let program = self.compile(facade, name, &mut files)?;
+        self.programs.insert(
+            name.to_owned(),
+            ShaderProgram {
+                program,
+                files: files
+                    .into_iter()
+                    .map(|path| {
+                        let time = modified(&path);
+                        (path, time)
+                    })
+                    .collect(),
+            },
+        );
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> &Program {
+        &self
+            .programs
+            .get(name)
+            .unwrap_or_else(|| panic!("Shader {} was never loaded", name))
+            .program
+    }
+
+    /// Recompiles the programs whose files changed on disk since they were loaded.
+    /// A program that fails to compile keeps running its last working version.
+    pub fn reload_changed(&mut self, facade: &dyn Facade) {
+        if self.last_check.elapsed() < RELOAD_INTERVAL {
+            return;
+        }
+        self.last_check = Instant::now();
+
+        let changed: Vec<String> = self
+            .programs
+            .iter()
+            .filter(|(_, program)| {
+                program
+                    .files
+                    .iter()
+                    .any(|(path, time)| modified(path) != *time)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for name in changed {
+            match self.load(facade, &name) {
+                Ok(()) => println!("Reloaded shader {}", name),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    // Don't try again until the files change some more
+                    for (path, time) in &mut self.programs.get_mut(&name).unwrap().files {
+                        *time = modified(path);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory holding `files`, named and contents
+    fn shader_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("mc_rs_shader_{}_{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        for (name, contents) in files {
+            let path = directory.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        directory
+    }
+
+    fn nested(test: &str) -> PathBuf {
+        shader_files(
+            test,
+            &[
+                (
+                    "main.glsl",
+                    "#version 140\n#include \"lib/a.glsl\"\nvoid main() {}\n",
+                ),
+                ("lib/a.glsl", "#include \"b.glsl\"\nfloat a;\n"),
+                ("lib/b.glsl", "float b;\n"),
+            ],
+        )
+    }
+
+    #[test]
+    fn nested_includes_are_pasted_in() {
+        let directory = nested("nested");
+        let mut files = Vec::new();
+        let source = Source::load(&directory.join("main.glsl"), &mut files).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            source.code,
+            "#version 140\nfloat b;\nfloat a;\nvoid main() {}\n"
+        );
+        // Includes are found next to the file including them
+        assert_eq!(
+            files,
+            [
+                directory.join("main.glsl"),
+                directory.join("lib/a.glsl"),
+                directory.join("lib/b.glsl"),
+            ]
+        );
+    }
+
+    #[test]
+    fn include_cycle_is_an_error() {
+        let directory = shader_files(
+            "cycle",
+            &[
+                ("main.glsl", "#include \"a.glsl\"\n"),
+                ("a.glsl", "#include \"b.glsl\"\n"),
+                ("b.glsl", "#include \"a.glsl\"\n"),
+            ],
+        );
+        let result = Source::load(&directory.join("main.glsl"), &mut Vec::new());
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let error = result.err().unwrap();
+        assert!(error.ends_with("a.glsl includes itself"), "{}", error);
+    }
+
+    #[test]
+    fn empty_include_is_an_error() {
+        let directory = shader_files("empty", &[("main.glsl", "#version 140\n#include \"\"\n")]);
+        let result = Source::load(&directory.join("main.glsl"), &mut Vec::new());
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let error = result.err().unwrap();
+        assert!(error.ends_with("main.glsl:2: empty #include"), "{}", error);
+    }
+
+    #[test]
+    fn log_lines_point_at_the_included_file() {
+        let directory = nested("log");
+        let source = Source::load(&directory.join("main.glsl"), &mut Vec::new()).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let log = "0:3(5): error: a is bad\n0(2) : error C0000: b is bad\nlink failed";
+        let expected = format!(
+            "{}:2: 0:3(5): error: a is bad\n{}:1: 0(2) : error C0000: b is bad\nlink failed",
+            directory.join("lib/a.glsl").display(),
+            directory.join("lib/b.glsl").display(),
+        );
+        assert_eq!(source.annotate_log(log), expected);
+        assert_eq!(Source::log_line_number("ERROR: 0:14: oops"), Some(14));
+    }
+}
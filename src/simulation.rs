@@ -0,0 +1,285 @@
+use std::collections::HashSet;
+
+use glium::glutin::event::{DeviceEvent, ElementState, MouseButton, VirtualKeyCode, WindowEvent};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::{Action, Actions, Bindings, HOTBAR_SLOTS},
+    block::BlockId,
+    gamepad::{Gamepad, GamepadEvent},
+    keyboard::Keyboard,
+    player::Player,
+    renderer::ATLAS_BLOCKS,
+    settings::Settings,
+    sky::{WorldTime, TICKS_PER_SECOND},
+    transform::Transform,
+    vector3::Vector3,
+    world::{RaycastHit, World, WorldMetadata},
+};
+
+/// Simulation ticks per second, a multiple of `TICKS_PER_SECOND`.
+pub const TICK_RATE: u32 = 60;
+pub const TICK_DURATION: f32 = 1.0 / TICK_RATE as f32;
+
+/// Longest stretch of time `TickClock` catches up on at once, after a hitch the game slows down
+/// for a moment instead of running a burst of ticks.
+pub const MAX_FRAME_TIME: f32 = 0.25;
+
+/// How far away blocks can be broken and placed.
+pub const REACH: f32 = 6.0;
+
+/// What `Action::Hotbar(n)` picks, the first slot is 1.
+pub const HOTBAR: [BlockId; HOTBAR_SLOTS as usize] = [
+    BlockId::Stone,
+    BlockId::Dirt,
+    BlockId::Grass,
+    BlockId::Sand,
+    BlockId::Log,
+    BlockId::Leaves,
+    BlockId::Glass,
+    BlockId::Lamp,
+    BlockId::Water,
+];
+
+/// Turns the time between frames into whole ticks, what's left over carries into the next frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TickClock {
+    accumulator: f32,
+}
+
+impl TickClock {
+    pub fn new() -> TickClock {
+        TickClock::default()
+    }
+
+    /// Adds `elapsed` seconds and returns how many ticks to run for them.
+    pub fn advance(&mut self, elapsed: f32) -> u32 {
+        self.accumulator += elapsed.clamp(0.0, MAX_FRAME_TIME);
+        let ticks = (self.accumulator / TICK_DURATION) as u32;
+        self.accumulator -= ticks as f32 * TICK_DURATION;
+        ticks
+    }
+
+    /// How far the clock is into the next tick, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / TICK_DURATION).clamp(0.0, 1.0)
+    }
+}
+
+/// Input the simulation reacts to, taken out of whatever event it came in.
+/// Mouse motion is kept as `f32`, the precision the player turns with, so a recording replays
+/// the exact same turns.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputEvent {
+    Key { key: VirtualKeyCode, pressed: bool },
+    MouseButton { button: MouseButton, pressed: bool },
+    MouseMotion { dx: f32, dy: f32 },
+    Gamepad { event: GamepadEvent },
+}
+
+impl InputEvent {
+    pub fn from_device_event(ev: &DeviceEvent) -> Option<InputEvent> {
+        match ev {
+            DeviceEvent::Key(input) => Some(InputEvent::Key {
+                key: input.virtual_keycode?,
+                pressed: input.state == ElementState::Pressed,
+            }),
+            DeviceEvent::MouseMotion { delta } => Some(InputEvent::MouseMotion {
+                dx: delta.0 as f32,
+                dy: delta.1 as f32,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn from_window_event(ev: &WindowEvent) -> Option<InputEvent> {
+        match ev {
+            WindowEvent::MouseInput { state, button, .. } => Some(InputEvent::MouseButton {
+                button: *button,
+                pressed: *state == ElementState::Pressed,
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn element_state(pressed: bool) -> ElementState {
+    if pressed {
+        ElementState::Pressed
+    } else {
+        ElementState::Released
+    }
+}
+
+/// A block broken or placed by the player, broken ones turn into air.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockEdit {
+    pub tick: u64,
+    pub position: [i64; 3],
+    pub block: BlockId,
+}
+
+/// Everything the game does apart from drawing, advanced in ticks of `TICK_DURATION`.
+/// The same events before the same ticks always end up in the same state, that's what replays
+/// rely on.
+pub struct Simulation {
+    pub world: World,
+    pub player: Player,
+    pub bindings: Bindings,
+    pub time: WorldTime,
+    /// Ticks run so far.
+    pub ticks: u64,
+    pub selected_block: BlockId,
+    /// Every block broken or placed, oldest first.
+    pub edits: Vec<BlockEdit>,
+    input: Keyboard,
+    gamepad: Gamepad,
+    actions: Actions,
+    /// Where the player was before the last tick, frames in between are drawn from here on.
+    previous_position: Vector3,
+    dirty_chunks: HashSet<[i64; 3]>,
+}
+
+impl Simulation {
+    /// Generates the world of `metadata` and puts the player above it.
+    pub fn new(metadata: &WorldMetadata, settings: &Settings, bindings: Bindings) -> Simulation {
+        let render_distance = settings.graphics.render_distance;
+        let generator = metadata.create_generator(&ATLAS_BLOCKS);
+        let mut world = World::new(render_distance);
+        for i in 0..render_distance as i64 {
+            for j in 0..render_distance as i64 {
+                for k in metadata.generator_settings.chunk_y_range() {
+                    world.generate_chunk([i, k, j], generator.as_ref());
+                }
+            }
+        }
+        world.compute_light();
+
+        let mut player = Player::from_settings(settings);
+        player.transform.position.z = 5.0;
+        player.transform.position.y = metadata.generator_settings.sea_level as f32 + 40.0;
+
+        Simulation {
+            world,
+            previous_position: player.transform.position,
+            player,
+            bindings,
+            time: WorldTime::default(),
+            ticks: 0,
+            selected_block: HOTBAR[0],
+            edits: Vec::new(),
+            input: Keyboard::new(),
+            gamepad: Gamepad::new(),
+            actions: Actions::new(),
+            dirty_chunks: HashSet::new(),
+        }
+    }
+
+    pub fn process_event(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::Key { key, pressed } => self.input.process_key(key, element_state(pressed)),
+            InputEvent::MouseButton { button, pressed } => self
+                .input
+                .process_mouse_button(button, element_state(pressed)),
+            InputEvent::MouseMotion { dx, dy } => self.player.mouse_look(dx, dy),
+            InputEvent::Gamepad { event } => self.gamepad.process_event(event),
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.previous_position = self.player.transform.position;
+        self.actions
+            .update(&self.bindings, &self.input, &self.gamepad);
+        self.player.update(&self.actions, TICK_DURATION);
+
+        for slot in 1..=HOTBAR_SLOTS {
+            if self.actions.is_just_active(Action::Hotbar(slot)) {
+                self.selected_block = HOTBAR[slot as usize - 1];
+            }
+        }
+        if self.actions.is_just_active(Action::Break) {
+            if let Some(hit) = self.aimed_block() {
+                self.edit(hit.block, BlockId::Air);
+            }
+        }
+        if self.actions.is_just_active(Action::Place) {
+            if let Some(hit) = self.aimed_block().filter(|hit| hit.normal != [0; 3]) {
+                let position: [i64; 3] = std::array::from_fn(|i| hit.block[i] + hit.normal[i]);
+                // Blocks go into water like into air
+                if matches!(
+                    self.world.get_block(position),
+                    Some(BlockId::Air | BlockId::Water)
+                ) {
+                    self.edit(position, self.selected_block);
+                }
+            }
+        }
+
+        self.input.end_frame();
+        self.ticks += 1;
+        if self
+            .ticks
+            .is_multiple_of((TICK_RATE / TICKS_PER_SECOND) as u64)
+        {
+            self.time.tick();
+        }
+    }
+
+    /// The player's transform `alpha` of the way from the last tick to the next one. Only the
+    /// position is blended, turning already happens as the mouse moves.
+    pub fn render_transform(&self, alpha: f32) -> Transform {
+        let mut transform = self.player.transform;
+        let current = transform.position;
+        transform.position = self.previous_position + (current - self.previous_position) * alpha;
+        transform
+    }
+
+    fn aimed_block(&self) -> Option<RaycastHit> {
+        let position = self.player.transform.position;
+        self.world.raycast(
+            [position.x, position.y, position.z],
+            self.player.look_direction(),
+            REACH,
+        )
+    }
+
+    fn edit(&mut self, position: [i64; 3], block: BlockId) {
+        let dirty = self.world.set_block(position, block);
+        if dirty.is_empty() {
+            return;
+        }
+        self.edits.push(BlockEdit {
+            tick: self.ticks,
+            position,
+            block,
+        });
+        self.dirty_chunks.extend(dirty);
+    }
+
+    /// Chunks changed since the last call, their meshes have to be rebuilt.
+    pub fn take_dirty_chunks(&mut self) -> HashSet<[i64; 3]> {
+        std::mem::take(&mut self.dirty_chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_runs_whole_ticks_and_keeps_the_rest() {
+        let mut clock = TickClock::new();
+        assert_eq!(clock.advance(TICK_DURATION * 2.5), 2);
+        assert!((clock.alpha() - 0.5).abs() < 1e-3);
+        assert_eq!(clock.advance(TICK_DURATION * 0.6), 1);
+        assert!((clock.alpha() - 0.1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn clock_catches_up_on_a_hitch_only_partly() {
+        let mut clock = TickClock::new();
+        let ticks = clock.advance(10.0);
+        assert_eq!(ticks, (MAX_FRAME_TIME / TICK_DURATION) as u32);
+    }
+}
@@ -0,0 +1,205 @@
+use glium::{
+    index::{NoIndices, PrimitiveType},
+    uniform,
+    vertex::EmptyVertexAttributes,
+    Program, Surface,
+};
+use nalgebra_glm as glm;
+
+use crate::{camera::Camera, transform, transform::Transform};
+
+pub const TICKS_PER_SECOND: u32 = 20;
+/// A full day and night take 20 minutes.
+pub const DAY_LENGTH: u64 = 24000;
+
+const DAY_SKY: [[f32; 3]; 2] = [[0.35, 0.55, 0.95], [0.8, 0.85, 1.0]];
+const SUNSET_SKY: [[f32; 3]; 2] = [[0.25, 0.3, 0.55], [0.95, 0.55, 0.35]];
+const NIGHT_SKY: [[f32; 3]; 2] = [[0.01, 0.01, 0.04], [0.05, 0.06, 0.12]];
+
+const SUN_COLOR: [f32; 3] = [1.0, 0.98, 0.9];
+const SUNSET_COLOR: [f32; 3] = [1.0, 0.6, 0.4];
+const MOON_COLOR: [f32; 3] = [0.3, 0.35, 0.5];
+
+/// How much of the sky light is left at midnight.
+const NIGHT_SKY_LIGHT: f32 = 0.2;
+
+/// Ticks since the world was created. Tick 0 is sunrise, noon is a quarter day later.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WorldTime {
+    pub ticks: u64,
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+impl WorldTime {
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    /// How far into the current day we are, from 0 to 1.
+    pub fn day_fraction(&self) -> f32 {
+        (self.ticks % DAY_LENGTH) as f32 / DAY_LENGTH as f32
+    }
+
+    /// Unit vector pointing at the sun. It rises in +x and sets in -x, slightly tilted towards +z.
+    pub fn sun_direction(&self) -> [f32; 3] {
+        let angle = self.day_fraction() * std::f32::consts::TAU;
+        let direction = glm::normalize(&glm::vec3(angle.cos(), angle.sin(), 0.3));
+        [direction.x, direction.y, direction.z]
+    }
+
+    /// Sun height above the horizon, -1 at midnight to 1 at noon.
+    fn sun_height(&self) -> f32 {
+        (self.day_fraction() * std::f32::consts::TAU).sin()
+    }
+
+    /// 0 at night, 1 during the day, in between while the sun is close to the horizon.
+    fn daylight(&self) -> f32 {
+        glm::smoothstep(-0.2, 0.2, self.sun_height())
+    }
+
+    /// How close the sun is to the horizon, 1 right on it.
+    fn twilight(&self) -> f32 {
+        1.0 - glm::smoothstep(0.0, 0.3, self.sun_height().abs())
+    }
+
+    /// The sun during the day and the moon at night.
+    pub fn light_direction(&self) -> [f32; 3] {
+        let sun = self.sun_direction();
+        if self.sun_height() >= 0.0 {
+            sun
+        } else {
+            [-sun[0], -sun[1], -sun[2]]
+        }
+    }
+
+    pub fn light_color(&self) -> [f32; 3] {
+        let color = mix(MOON_COLOR, SUN_COLOR, self.daylight());
+        mix(color, SUNSET_COLOR, self.twilight() * self.daylight())
+    }
+
+    /// Zenith and horizon colors of the sky.
+    pub fn sky_colors(&self) -> [[f32; 3]; 2] {
+        let daylight = self.daylight();
+        let twilight = self.twilight();
+        [0, 1].map(|i| {
+            let color = mix(NIGHT_SKY[i], DAY_SKY[i], daylight);
+            mix(color, SUNSET_SKY[i], twilight * 0.8)
+        })
+    }
+
+    /// Factor sky light is dimmed by, so caves lit by lamps stay as bright at night.
+    pub fn sky_brightness(&self) -> f32 {
+        NIGHT_SKY_LIGHT + (1.0 - NIGHT_SKY_LIGHT) * self.daylight()
+    }
+
+    pub fn lighting(&self, fog_start: f32, fog_end: f32) -> SceneLighting {
+        SceneLighting {
+            light_direction: self.light_direction(),
+            light_color: self.light_color(),
+            sky_brightness: self.sky_brightness(),
+            fog_color: self.sky_colors()[1],
+            fog_start,
+            fog_end,
+        }
+    }
+}
+
+/// Everything the mesh shader needs to know about the sun, the sky and the fog.
+#[derive(Clone, Copy, Debug)]
+pub struct SceneLighting {
+    pub light_direction: [f32; 3],
+    pub light_color: [f32; 3],
+    pub sky_brightness: f32,
+    pub fog_color: [f32; 3],
+    /// Distance from the camera where fog starts to show.
+    pub fog_start: f32,
+    /// Distance from the camera where the fog hides everything.
+    pub fog_end: f32,
+}
+
+/// Draws the sky gradient and the sun over the whole screen, meant to go before everything else.
+pub fn draw_sky<S: Surface>(
+    surface: &mut S,
+    shader_program: &Program,
+    camera: &dyn Camera,
+    camera_transform: Transform,
+    time: &WorldTime,
+) {
+    // Only the rotation matters, the sky is infinitely far away
+    let mut view = camera.view(camera_transform);
+    view.set_column(3, &glm::vec4(0.0, 0.0, 0.0, 1.0));
+    let inverse_view_projection = glm::inverse(&(camera.projection() * view));
+    let [zenith, horizon] = time.sky_colors();
+
+    surface
+        .draw(
+            EmptyVertexAttributes { len: 3 },
+            NoIndices(PrimitiveType::TrianglesList),
+            shader_program,
+            &uniform! {
+                inverse_view_projection: transform::mat2array(inverse_view_projection),
+                zenith_color: zenith,
+                horizon_color: horizon,
+                sun_direction: time.sun_direction(),
+                sun_color: time.light_color(),
+            },
+            &Default::default(),
+        )
+        .expect("Failed to draw sky");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOON: u64 = DAY_LENGTH / 4;
+    const MIDNIGHT: u64 = DAY_LENGTH * 3 / 4;
+
+    fn at(ticks: u64) -> WorldTime {
+        WorldTime { ticks }
+    }
+
+    #[test]
+    fn days_wrap_around() {
+        assert_eq!(at(0).day_fraction(), 0.0);
+        assert_eq!(at(DAY_LENGTH).day_fraction(), 0.0);
+        assert_eq!(at(DAY_LENGTH * 3 + NOON).day_fraction(), 0.25);
+        assert_eq!(
+            at(DAY_LENGTH - 1).day_fraction(),
+            (DAY_LENGTH - 1) as f32 / DAY_LENGTH as f32
+        );
+        assert_eq!(
+            at(DAY_LENGTH + MIDNIGHT).sun_direction(),
+            at(MIDNIGHT).sun_direction()
+        );
+    }
+
+    #[test]
+    fn sun_rises_in_east_and_is_overhead_at_noon() {
+        let sunrise = at(0).sun_direction();
+        assert!(sunrise[0] > 0.9 && sunrise[1].abs() < 1e-6);
+        let noon = at(NOON).sun_direction();
+        assert!(noon[1] > 0.9 && noon[0].abs() < 1e-6);
+        // At midnight the sun is below and the moon lights the world from above
+        let midnight = at(MIDNIGHT);
+        assert!(midnight.sun_direction()[1] < -0.9);
+        assert!(midnight.light_direction()[1] > 0.9);
+    }
+
+    #[test]
+    fn sky_light_is_full_at_noon_and_dim_at_midnight() {
+        assert_eq!(at(NOON).sky_brightness(), 1.0);
+        assert_eq!(at(MIDNIGHT).sky_brightness(), NIGHT_SKY_LIGHT);
+        assert_eq!(at(NOON).sky_colors(), DAY_SKY);
+        assert_eq!(at(MIDNIGHT).sky_colors(), NIGHT_SKY);
+        assert_eq!(at(NOON).light_color(), SUN_COLOR);
+        assert_eq!(at(MIDNIGHT).light_color(), MOON_COLOR);
+    }
+}
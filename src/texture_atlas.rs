@@ -13,7 +13,7 @@ pub struct TextureAtlas {
     numof_blocks: usize,
     numof_block_faces: usize,
     id_texture_map: HashMap<BlockId, BlockUv>,
-    texture: glium::texture::SrgbTexture2d,
+    texture: Option<glium::texture::SrgbTexture2d>,
 }
 
 impl TextureAtlas {
@@ -37,7 +37,7 @@ impl TextureAtlas {
                 let image =
                     glium::texture::RawImage2d::from_raw_rgba(image.into_raw(), image_dimensions);
 
-                glium::texture::SrgbTexture2d::new(display, image).unwrap()
+                Some(glium::texture::SrgbTexture2d::new(display, image).unwrap())
             },
 
             id_texture_map: HashMap::new(),
@@ -46,7 +46,18 @@ impl TextureAtlas {
         }
     }
 
-    pub fn with_blocks(mut self, numof_block_faces: usize, blocks: &Vec<BlockId>) -> Self {
+    /// Atlas that only knows where the blocks are, for meshing without a GL context.
+    pub fn without_texture(filename: String) -> TextureAtlas {
+        TextureAtlas {
+            filename,
+            texture: None,
+            id_texture_map: HashMap::new(),
+            numof_blocks: 0,
+            numof_block_faces: 0,
+        }
+    }
+
+    pub fn with_blocks(mut self, numof_block_faces: usize, blocks: &[BlockId]) -> Self {
         self.numof_blocks = blocks.len();
         self.numof_block_faces = numof_block_faces;
         let w = 1.0 / self.numof_blocks as f32;
@@ -167,13 +178,20 @@ impl TextureAtlas {
     }
 
     pub fn get_texture(&self) -> &SrgbTexture2d {
-        &self.texture
+        self.texture
+            .as_ref()
+            .expect("Texture atlas was made without a texture")
     }
 
     pub fn get_block_uv(&self, block: BlockId) -> &BlockUv {
         self.id_texture_map
             .get(&block)
-            .expect(format!("Block {:?} no configured!", block).as_str())
+            .unwrap_or_else(|| panic!("Block {:?} no configured!", block))
+    }
+
+    /// Number of textures across and down the atlas, one column per block and one row per face.
+    pub fn tiles(&self) -> [u32; 2] {
+        [self.numof_blocks as u32, self.numof_block_faces as u32]
     }
 }
 
@@ -1,10 +1,12 @@
 use crate::vector3::Vector3;
 use nalgebra_glm as glm;
 
+/// Where something is, which way it faces and how big it is. Facing nowhere in particular
+/// means looking down -z with +y up.
 #[derive(Clone, Copy)]
 pub struct Transform {
     pub position: Vector3,
-    rotation: Vector3,
+    pub rotation: glm::Quat,
     pub scale: Vector3,
 }
 
@@ -16,11 +18,7 @@ impl Transform {
                 y: 0f32,
                 z: 0f32,
             },
-            rotation: Vector3 {
-                x: 0f32,
-                y: 0f32,
-                z: 0f32,
-            },
+            rotation: glm::quat_identity(),
             scale: Vector3 {
                 x: 1f32,
                 y: 1f32,
@@ -28,48 +26,39 @@ impl Transform {
             },
         }
     }
-    pub fn to_matrix(&self) -> glm::Mat4 {
-        let mut r = glm::identity();
-
-        r = glm::scale(&r, &self.scale.into());
-
-        r = glm::rotate(&r, self.rotation.x, &glm::Vec3::x());
-        r = glm::rotate(&r, self.rotation.y, &glm::Vec3::y());
-        r = glm::rotate(&r, self.rotation.z, &glm::Vec3::z());
 
-        r = glm::translate(&r, &self.position.into());
-
-        r
-    }
-
-    pub fn rotate_x(&mut self, angle: f32) {
-        self.rotation.x += angle % 360f32;
-    }
-
-    pub fn rotate_y(&mut self, angle: f32) {
-        self.rotation.y += angle % 360f32;
+    /// Scales, then rotates, then moves into place.
+    pub fn to_matrix(&self) -> glm::Mat4 {
+        let mut r = glm::translation(&self.position.into());
+        r *= glm::quat_to_mat4(&self.rotation);
+        glm::scale(&r, &self.scale.into())
     }
 
-    pub fn rotate_z(&mut self, angle: f32) {
-        self.rotation.z += angle % 360f32;
+    /// Turns around `axis` in world space, `angle` in radians.
+    pub fn rotate(&mut self, angle: f32, axis: Vector3) {
+        let turn = glm::quat_angle_axis(angle, &axis.into());
+        self.rotation = glm::quat_normalize(&(turn * self.rotation));
     }
 
-    pub fn get_rotation(&self) -> Vector3 {
-        self.rotation
+    /// Faces `yaw` radians counterclockwise from -z seen from above, then `pitch` radians up.
+    pub fn set_yaw_pitch(&mut self, yaw: f32, pitch: f32) {
+        self.rotation = glm::quat_angle_axis(yaw, &glm::Vec3::y())
+            * glm::quat_angle_axis(pitch, &glm::Vec3::x());
     }
 
-    pub fn set_rotation_x(&mut self, angle: f32) {
-        self.rotation.x = angle % 360f32;
+    pub fn forward(&self) -> Vector3 {
+        glm::quat_rotate_vec3(&self.rotation, &-glm::Vec3::z()).into()
     }
 
-    pub fn set_rotation_y(&mut self, angle: f32) {
-        self.rotation.y = angle % 360f32;
+    pub fn right(&self) -> Vector3 {
+        glm::quat_rotate_vec3(&self.rotation, &glm::Vec3::x()).into()
     }
 
-    pub fn set_rotation_z(&mut self, angle: f32) {
-        self.rotation.z = angle % 360f32;
+    pub fn up(&self) -> Vector3 {
+        glm::quat_rotate_vec3(&self.rotation, &glm::Vec3::y()).into()
     }
 }
+
 pub fn mat2array(m: glm::Mat4) -> [[f32; 4]; 4] {
     let mut r: [[f32; 4]; 4] = [[0f32; 4]; 4];
     for i in 0..4 {
@@ -80,3 +69,58 @@ pub fn mat2array(m: glm::Mat4) -> [[f32; 4]; 4] {
 
     r
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vector3, b: [f32; 3]) {
+        let a: [f32; 3] = a.into();
+        assert!(
+            (0..3).all(|i| (a[i] - b[i]).abs() < 1e-5),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn yaw_turns_left_and_pitch_up() {
+        let mut transform = Transform::zero();
+        assert_near(transform.forward(), [0.0, 0.0, -1.0]);
+
+        transform.set_yaw_pitch(90f32.to_radians(), 0.0);
+        assert_near(transform.forward(), [-1.0, 0.0, 0.0]);
+        assert_near(transform.right(), [0.0, 0.0, -1.0]);
+        assert_near(transform.up(), [0.0, 1.0, 0.0]);
+
+        transform.set_yaw_pitch(0.0, 90f32.to_radians());
+        assert_near(transform.forward(), [0.0, 1.0, 0.0]);
+        assert_near(transform.up(), [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn matrix_moves_after_scaling_and_rotating() {
+        let mut transform = Transform::zero();
+        transform.position = Vector3 {
+            x: 10.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        transform.scale = Vector3 {
+            x: 2.0,
+            y: 2.0,
+            z: 2.0,
+        };
+        transform.rotate(
+            90f32.to_radians(),
+            Vector3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+        );
+        let point = transform.to_matrix() * glm::vec4(1.0, 0.0, 0.0, 1.0);
+        assert_near(point.xyz().into(), [10.0, 0.0, -2.0]);
+    }
+}
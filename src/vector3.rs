@@ -72,6 +72,16 @@ impl Into<glm::Vec3> for Vector3 {
     }
 }
 
+impl From<glm::Vec3> for Vector3 {
+    fn from(v: glm::Vec3) -> Vector3 {
+        Vector3 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+}
+
 impl Into<[f32; 3]> for Vector3 {
     fn into(self) -> [f32; 3] {
         [self.x, self.y, self.z]
@@ -1,10 +1,20 @@
-use crate::vector3::Vector3;
+use crate::{vector3::Vector3, visibility::FACES};
+
+/// Vertices a `Mesh` can be made of.
+pub trait MeshVertex: glium::Vertex + Copy {
+    /// Where the vertex is, before the mesh transform.
+    fn position(&self) -> [f32; 3];
+}
 
 #[derive(Copy, Clone, Debug, Default)]
 pub struct Vertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
     pub uv: [f32; 2],
+    /// Sky and block light between 0 and 1.
+    pub light: [f32; 2],
+    /// Ambient occlusion, 0 in a tight corner and 1 out in the open.
+    pub ao: f32,
 }
 
 impl Vertex {
@@ -22,6 +32,8 @@ impl Vertex {
             position: [pos_x, pos_y, pos_z],
             normal: [normal_x, normal_y, normal_z],
             uv: [uv_x, uv_y],
+            light: [1.0, 0.0],
+            ao: 1.0,
         }
     }
 
@@ -30,7 +42,215 @@ impl Vertex {
             position: [pos.x, pos.y, pos.z],
             normal: [norm.x, norm.y, norm.z],
             uv,
+            light: [1.0, 0.0],
+            ao: 1.0,
+        }
+    }
+
+    pub fn with_light(mut self, sky: f32, block: f32) -> Vertex {
+        self.light = [sky, block];
+        self
+    }
+
+    pub fn with_ao(mut self, ao: f32) -> Vertex {
+        self.ao = ao;
+        self
+    }
+}
+glium::implement_vertex!(Vertex, position, normal, uv, light, ao);
+
+impl MeshVertex for Vertex {
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+}
+
+/// Chunk mesh vertex squeezed into 8 bytes, unpacked again by the chunk vertex shader.
+/// Bits of `data`, lowest first:
+/// - 5 each for x, y and z, corners of the blocks in the chunk from 0 to 16
+/// - 3 for the face, an index into `FACES`
+/// - 1 each for the texture corner along u and v
+/// - 2 for ambient occlusion, 0 to 3
+/// - 4 each for sky and block light
+///
+/// `tile` is the texture in the atlas, counting row by row.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChunkVertex {
+    pub data: u32,
+    pub tile: u32,
+}
+
+impl ChunkVertex {
+    /// Packs a vertex of a block face, with its light and ambient occlusion already set.
+    /// `corner` is the texture corner and `tile` the texture, see `pack_quad`.
+    pub fn pack(vertex: &Vertex, corner: [u32; 2], tile: u32) -> ChunkVertex {
+        // Blocks are centered on whole numbers, their corners are halfway between
+        let [x, y, z] = vertex.position.map(|p| (p + 0.5).round() as u32);
+        let normal = vertex.normal.map(|n| n.round() as i64);
+        let face = FACES
+            .iter()
+            .position(|face| *face == normal)
+            .expect("Chunk vertex normal has to point along an axis") as u32;
+        let ao = (vertex.ao * 3.0).round() as u32;
+        let [sky, block] = vertex.light.map(|l| (l * 15.0).round() as u32);
+
+        ChunkVertex {
+            data: x
+                | y << 5
+                | z << 10
+                | face << 15
+                | corner[0] << 18
+                | corner[1] << 19
+                | ao << 20
+                | sky << 22
+                | block << 26,
+            tile,
+        }
+    }
+
+    /// Packs a block face whose vertices have atlas UVs, for an atlas `tiles` textures wide and
+    /// high. The texture is the one the UVs lie in and each corner is worked out from where its
+    /// UV sits in it.
+    pub fn pack_quad(quad: &[Vertex; 4], tiles: [u32; 2]) -> [ChunkVertex; 4] {
+        let cells =
+            quad.map(|vertex| [0, 1].map(|i| (vertex.uv[i] * tiles[i] as f32).round() as u32));
+        let tile = [0, 1].map(|i| cells.iter().map(|cell| cell[i]).min().unwrap());
+        std::array::from_fn(|i| {
+            ChunkVertex::pack(
+                &quad[i],
+                [cells[i][0] - tile[0], cells[i][1] - tile[1]],
+                tile[0] + tile[1] * tiles[0],
+            )
+        })
+    }
+
+    pub fn local_position(&self) -> [u32; 3] {
+        [self.data & 31, self.data >> 5 & 31, self.data >> 10 & 31]
+    }
+}
+
+impl MeshVertex for ChunkVertex {
+    fn position(&self) -> [f32; 3] {
+        self.local_position().map(|p| p as f32 - 0.5)
+    }
+}
+glium::implement_vertex!(ChunkVertex, data, tile);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Everything the chunk vertex shader reads out of a packed vertex
+    #[derive(Debug, PartialEq)]
+    struct Unpacked {
+        position: [u32; 3],
+        face: u32,
+        corner: [u32; 2],
+        ao: u32,
+        light: [u32; 2],
+    }
+
+    fn unpack(vertex: ChunkVertex) -> Unpacked {
+        let data = vertex.data;
+        Unpacked {
+            position: vertex.local_position(),
+            face: data >> 15 & 7,
+            corner: [data >> 18 & 1, data >> 19 & 1],
+            ao: data >> 20 & 3,
+            light: [data >> 22 & 15, data >> 26 & 15],
+        }
+    }
+
+    fn vertex(position: [f32; 3], normal: [f32; 3]) -> Vertex {
+        Vertex {
+            position,
+            normal,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn packed_fields_round_trip_at_their_extremes() {
+        let top = vertex([15.5, 15.5, 15.5], [0.0, 0.0, -1.0])
+            .with_ao(1.0)
+            .with_light(1.0, 1.0);
+        let packed = ChunkVertex::pack(&top, [1, 1], u32::MAX);
+        assert_eq!(
+            unpack(packed),
+            Unpacked {
+                position: [16, 16, 16],
+                face: 5,
+                corner: [1, 1],
+                ao: 3,
+                light: [15, 15],
+            }
+        );
+        assert_eq!(packed.tile, u32::MAX);
+        assert_eq!(packed.position(), [15.5, 15.5, 15.5]);
+
+        let bottom = vertex([-0.5, -0.5, -0.5], [1.0, 0.0, 0.0])
+            .with_ao(0.0)
+            .with_light(0.0, 0.0);
+        let packed = ChunkVertex::pack(&bottom, [0, 0], 0);
+        assert_eq!(packed, ChunkVertex::default());
+    }
+
+    #[test]
+    fn packed_fields_dont_spill_into_each_other() {
+        let mixed = vertex([15.5, -0.5, 15.5], [0.0, 0.0, -1.0])
+            .with_ao(0.0)
+            .with_light(1.0, 0.0);
+        assert_eq!(
+            unpack(ChunkVertex::pack(&mixed, [1, 0], 7)),
+            Unpacked {
+                position: [16, 0, 16],
+                face: 5,
+                corner: [1, 0],
+                ao: 0,
+                light: [15, 0],
+            }
+        );
+        let mixed = vertex([-0.5, 15.5, -0.5], [0.0, 1.0, 0.0])
+            .with_ao(1.0)
+            .with_light(0.0, 1.0);
+        assert_eq!(
+            unpack(ChunkVertex::pack(&mixed, [0, 1], 7)),
+            Unpacked {
+                position: [0, 16, 0],
+                face: 2,
+                corner: [0, 1],
+                ao: 3,
+                light: [0, 15],
+            }
+        );
+    }
+
+    #[test]
+    fn quad_corners_follow_the_atlas_uvs() {
+        // Tile (2, 1) of a 4 by 4 atlas, corners in the order `TextureAtlas` hands them out
+        let uvs = [[0.75, 0.25], [0.75, 0.5], [0.5, 0.5], [0.5, 0.25]];
+        let positions = [
+            [0.5, 0.5, -0.5],
+            [0.5, 0.5, 0.5],
+            [-0.5, 0.5, 0.5],
+            [-0.5, 0.5, -0.5],
+        ];
+        let quad = std::array::from_fn(|i| Vertex {
+            uv: uvs[i],
+            ..vertex(positions[i], [0.0, 1.0, 0.0])
+        });
+
+        let packed = ChunkVertex::pack_quad(&quad, [4, 4]);
+        assert_eq!(
+            packed.map(|vertex| unpack(vertex).corner),
+            [[1, 0], [1, 1], [0, 1], [0, 0]]
+        );
+        for (vertex, uv) in packed.iter().zip(uvs) {
+            assert_eq!(vertex.tile, 6);
+            // What the shader does to get the UV back
+            let origin = [vertex.tile % 4, vertex.tile / 4];
+            let corner = unpack(*vertex).corner;
+            assert_eq!([0, 1].map(|i| (origin[i] + corner[i]) as f32 / 4.0), uv);
         }
     }
 }
-glium::implement_vertex!(Vertex, position, normal, uv);
@@ -0,0 +1,248 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::chunk::{Chunk, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+
+/// Directions to the six neighbors of a chunk, in x, y, z chunk coordinates.
+/// A face of a chunk is referred to by the index of the direction it faces.
+pub const FACES: [[i64; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+fn opposite(face: usize) -> usize {
+    face ^ 1
+}
+
+/// Which pairs of faces of a chunk can see each other through the blocks that aren't opaque.
+/// Cave culling walks this from the camera, so whatever is walled off by solid rock never
+/// gets drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkVisibility(u64);
+
+impl ChunkVisibility {
+    pub fn none() -> ChunkVisibility {
+        ChunkVisibility(0)
+    }
+
+    pub fn all() -> ChunkVisibility {
+        ChunkVisibility((1 << 36) - 1)
+    }
+
+    pub fn connects(&self, from: usize, to: usize) -> bool {
+        self.0 & (1 << (from * 6 + to)) != 0
+    }
+
+    fn connect(&mut self, from: usize, to: usize) {
+        self.0 |= 1 << (from * 6 + to);
+        self.0 |= 1 << (to * 6 + from);
+    }
+
+    /// Flood fills every pocket of non opaque blocks, connecting all the faces it touches.
+    pub fn compute(chunk: &Chunk) -> ChunkVisibility {
+        let mut visibility = ChunkVisibility::none();
+        let mut visited = [[[false; CHUNK_DEPTH]; CHUNK_WIDTH]; CHUNK_HEIGHT];
+        let size = [CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH];
+
+        // Faces the block at x, y, z is right against
+        let touched_faces = |block: [usize; 3]| -> u8 {
+            (0..6)
+                .filter(|face| {
+                    let axis = face / 2;
+                    if FACES[*face][axis] > 0 {
+                        block[axis] == size[axis] - 1
+                    } else {
+                        block[axis] == 0
+                    }
+                })
+                .fold(0, |faces, face| faces | 1 << face)
+        };
+
+        for h in 0..CHUNK_HEIGHT {
+            for w in 0..CHUNK_WIDTH {
+                for d in 0..CHUNK_DEPTH {
+                    if visited[h][w][d] || chunk.data[h][w][d].is_opaque() {
+                        continue;
+                    }
+
+                    let mut faces = 0u8;
+                    let mut queue = VecDeque::from([[w, h, d]]);
+                    visited[h][w][d] = true;
+                    while let Some(block) = queue.pop_front() {
+                        faces |= touched_faces(block);
+                        for direction in FACES {
+                            let neighbor: [usize; 3] = std::array::from_fn(|i| {
+                                block[i].wrapping_add_signed(direction[i] as isize)
+                            });
+                            if (0..3).any(|i| neighbor[i] >= size[i]) {
+                                continue;
+                            }
+                            let [x, y, z] = neighbor;
+                            if visited[y][x][z] || chunk.data[y][x][z].is_opaque() {
+                                continue;
+                            }
+                            visited[y][x][z] = true;
+                            queue.push_back(neighbor);
+                        }
+                    }
+
+                    for from in 0..6 {
+                        for to in 0..6 {
+                            if faces & (1 << from) != 0 && faces & (1 << to) != 0 {
+                                visibility.connect(from, to);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        visibility
+    }
+}
+
+/// Walks out from the chunk the camera is in, through the faces of every chunk that see each
+/// other, and returns the chunks that could be visible. The walk never turns back towards the
+/// camera and doesn't go into chunks `in_view` rejects.
+/// `None` if the camera isn't in a chunk `visibility` knows about, then nothing can be culled.
+pub fn visible_chunks(
+    camera_chunk: [i64; 3],
+    visibility: impl Fn([i64; 3]) -> Option<ChunkVisibility>,
+    in_view: impl Fn([i64; 3]) -> bool,
+) -> Option<HashSet<[i64; 3]>> {
+    visibility(camera_chunk)?;
+
+    let mut visible = HashSet::from([camera_chunk]);
+    // Chunk, face it was entered through, directions taken to get there
+    let mut queue: VecDeque<([i64; 3], Option<usize>, u8)> =
+        VecDeque::from([(camera_chunk, None, 0)]);
+    while let Some((position, entered_through, directions)) = queue.pop_front() {
+        let chunk_visibility = visibility(position).unwrap();
+        for (face, direction) in FACES.iter().enumerate() {
+            if directions & (1 << opposite(face)) != 0 {
+                continue;
+            }
+            if let Some(entry) = entered_through {
+                if !chunk_visibility.connects(entry, face) {
+                    continue;
+                }
+            }
+
+            let neighbor = [
+                position[0] + direction[0],
+                position[1] + direction[1],
+                position[2] + direction[2],
+            ];
+            if visible.contains(&neighbor) || visibility(neighbor).is_none() || !in_view(neighbor) {
+                continue;
+            }
+            visible.insert(neighbor);
+            queue.push_back((neighbor, Some(opposite(face)), directions | 1 << face));
+        }
+    }
+    Some(visible)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{block::BlockId, transform::Transform};
+
+    fn filled(block: BlockId) -> Chunk {
+        let mut chunk = Chunk::new(&Transform::zero());
+        chunk.data = [[[block; CHUNK_DEPTH]; CHUNK_WIDTH]; CHUNK_HEIGHT];
+        chunk
+    }
+
+    // The face pairs other than a face with itself that `visibility` connects
+    fn connected_pairs(visibility: ChunkVisibility) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for from in 0..6 {
+            for to in 0..6 {
+                if from != to && visibility.connects(from, to) {
+                    pairs.push((from, to));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn air_connects_every_face() {
+        assert_eq!(
+            ChunkVisibility::compute(&filled(BlockId::Air)),
+            ChunkVisibility::all()
+        );
+        // Blocks you can see through don't wall anything off
+        assert_eq!(
+            ChunkVisibility::compute(&filled(BlockId::Glass)),
+            ChunkVisibility::all()
+        );
+    }
+
+    #[test]
+    fn solid_connects_nothing() {
+        assert_eq!(
+            ChunkVisibility::compute(&filled(BlockId::Stone)),
+            ChunkVisibility::none()
+        );
+    }
+
+    #[test]
+    fn tunnel_connects_its_two_ends() {
+        let mut chunk = filled(BlockId::Stone);
+        for w in 0..CHUNK_WIDTH {
+            chunk.data[8][w][8] = BlockId::Air;
+        }
+        // A pocket that doesn't reach any face connects nothing
+        chunk.data[2][2][2] = BlockId::Air;
+
+        let visibility = ChunkVisibility::compute(&chunk);
+        assert_eq!(connected_pairs(visibility), [(0, 1), (1, 0)]);
+    }
+
+    // Walks a y = 0 layer of chunks, where `layout` has a row per z, and '#' is a solid chunk
+    fn walk(layout: &[&str], camera_chunk: [i64; 3]) -> Option<HashSet<[i64; 3]>> {
+        let mut chunks = HashMap::new();
+        for (z, row) in layout.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                let visibility = match cell {
+                    '#' => ChunkVisibility::none(),
+                    _ => ChunkVisibility::all(),
+                };
+                chunks.insert([x as i64, 0, z as i64], visibility);
+            }
+        }
+        visible_chunks(
+            camera_chunk,
+            |position| chunks.get(&position).copied(),
+            |_| true,
+        )
+    }
+
+    #[test]
+    fn sealed_wall_stops_the_walk() {
+        let visible = walk(&["..#.."], [0, 0, 0]).unwrap();
+        // The wall itself can be seen, what's behind it can't
+        assert_eq!(visible, HashSet::from([[0, 0, 0], [1, 0, 0], [2, 0, 0]]));
+    }
+
+    #[test]
+    fn walk_never_turns_back_towards_the_camera() {
+        // Going around the wall to reach the chunk behind it would need a step back in -z
+        let visible = walk(&[".#.", "..."], [0, 0, 0]).unwrap();
+        assert_eq!(
+            visible,
+            HashSet::from([[0, 0, 0], [1, 0, 0], [0, 0, 1], [1, 0, 1], [2, 0, 1]])
+        );
+    }
+
+    #[test]
+    fn walk_needs_a_known_camera_chunk() {
+        assert_eq!(walk(&["..."], [0, 0, 5]), None);
+    }
+}
@@ -1,7 +1,398 @@
-use crate::chunk::Chunk;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
-const RENDER_DISTANCE: i32 = 12;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::BlockId,
+    chunk::{Chunk, ChunkNeighbors, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH},
+    debug_generator::DebugGenerator,
+    flat_generator::FlatGenerator,
+    generator_settings::GeneratorSettings,
+    transform::Transform,
+    world_generator::{TerrainGenerator, WorldGenerator},
+};
 
 pub struct World {
     render_distance: u32,
+    pub(crate) chunks: HashMap<[i64; 3], Chunk>,
+}
+
+impl World {
+    pub fn new(render_distance: u32) -> World {
+        World {
+            render_distance,
+            chunks: HashMap::new(),
+        }
+    }
+
+    pub fn render_distance(&self) -> u32 {
+        self.render_distance
+    }
+
+    /// Generates the chunk at `position` (in chunk units), replacing whatever was there.
+    pub fn generate_chunk(&mut self, position: [i64; 3], generator: &dyn TerrainGenerator) {
+        let mut transform = Transform::zero();
+        transform.position.x = position[0] as f32;
+        transform.position.y = position[1] as f32;
+        transform.position.z = position[2] as f32;
+
+        let mut chunk = Chunk::new(&transform);
+        chunk.generate_data(generator);
+        self.chunks.insert(position, chunk);
+    }
+
+    pub fn get_chunk(&self, position: [i64; 3]) -> Option<&Chunk> {
+        self.chunks.get(&position)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values()
+    }
+
+    /// Splits a point in world coordinates into the position of its chunk
+    /// and the `h`, `w`, `d` index inside of it.
+    pub fn split_point(point: [i64; 3]) -> ([i64; 3], [usize; 3]) {
+        (
+            [
+                point[0].div_euclid(CHUNK_WIDTH as i64),
+                point[1].div_euclid(CHUNK_HEIGHT as i64),
+                point[2].div_euclid(CHUNK_DEPTH as i64),
+            ],
+            [
+                point[1].rem_euclid(CHUNK_HEIGHT as i64) as usize,
+                point[0].rem_euclid(CHUNK_WIDTH as i64) as usize,
+                point[2].rem_euclid(CHUNK_DEPTH as i64) as usize,
+            ],
+        )
+    }
+
+    /// Every point of the chunk starting at `origin`, in world coordinates.
+    pub fn chunk_points(origin: [i64; 3]) -> impl Iterator<Item = [i64; 3]> {
+        (0..CHUNK_HEIGHT as i64).flat_map(move |h| {
+            (0..CHUNK_WIDTH as i64).flat_map(move |w| {
+                (0..CHUNK_DEPTH as i64).map(move |d| [origin[0] + w, origin[1] + h, origin[2] + d])
+            })
+        })
+    }
+
+    /// `None` if the chunk holding `point` is not loaded.
+    pub fn get_block(&self, point: [i64; 3]) -> Option<BlockId> {
+        let (position, [h, w, d]) = World::split_point(point);
+        self.get_chunk(position).map(|chunk| chunk.data[h][w][d])
+    }
+
+    /// Changes a block and updates the light around it.
+    /// Returns the positions of the chunks whose mesh has to be rebuilt.
+    pub fn set_block(&mut self, point: [i64; 3], block: BlockId) -> HashSet<[i64; 3]> {
+        let mut dirty = HashSet::new();
+        let (position, [h, w, d]) = World::split_point(point);
+        let Some(chunk) = self.chunks.get_mut(&position) else {
+            return dirty;
+        };
+
+        let old = chunk.data[h][w][d];
+        if old == block {
+            return dirty;
+        }
+        chunk.data[h][w][d] = block;
+        World::mark_dirty(point, &mut dirty);
+        self.update_light(point, old, block, &mut dirty);
+
+        dirty.retain(|position| self.chunks.contains_key(position));
+        dirty
+    }
+
+    /// Walks the blocks along a ray, nearest first, and returns the first one that can be aimed
+    /// at. Air and water can't be, the ray goes through them.
+    pub fn raycast(
+        &self,
+        origin: [f32; 3],
+        direction: [f32; 3],
+        max_distance: f32,
+    ) -> Option<RaycastHit> {
+        let length = direction.iter().map(|d| d * d).sum::<f32>().sqrt();
+        if length == 0.0 {
+            return None;
+        }
+        let direction = direction.map(|d| d / length);
+
+        // Blocks are centered on whole numbers, half a block over their edges are whole numbers
+        let start = origin.map(|o| o + 0.5);
+        let mut block = start.map(|s| s.floor() as i64);
+        let step = direction.map(|d| {
+            if d > 0.0 {
+                1
+            } else if d < 0.0 {
+                -1
+            } else {
+                0
+            }
+        });
+        // Distance along the ray to the next block boundary on each axis, and between boundaries
+        let mut t_max: [f32; 3] = std::array::from_fn(|i| match step[i] {
+            1 => (block[i] as f32 + 1.0 - start[i]) / direction[i],
+            -1 => (block[i] as f32 - start[i]) / direction[i],
+            _ => f32::INFINITY,
+        });
+        let t_delta = direction.map(|d| 1.0 / d.abs());
+        let mut normal = [0; 3];
+        let mut distance = 0.0;
+
+        loop {
+            if let Some(found) = self.get_block(block) {
+                if found != BlockId::Air && found != BlockId::Water {
+                    return Some(RaycastHit {
+                        block,
+                        normal,
+                        distance,
+                    });
+                }
+            }
+
+            let axis = (0..3).min_by(|a, b| t_max[*a].total_cmp(&t_max[*b]))?;
+            if t_max[axis] > max_distance {
+                return None;
+            }
+            distance = t_max[axis];
+            block[axis] += step[axis];
+            normal = [0; 3];
+            normal[axis] = -step[axis];
+            t_max[axis] += t_delta[axis];
+        }
+    }
+
+    /// Adds every chunk holding `point` or one of the 26 blocks around it to `dirty`, they all
+    /// look at it when building their mesh, the diagonal ones for ambient occlusion.
+    pub(crate) fn mark_dirty(point: [i64; 3], dirty: &mut HashSet<[i64; 3]>) {
+        let (position, [h, w, d]) = World::split_point(point);
+        let offsets = |index: usize, size: usize| {
+            let mut offsets = vec![0];
+            if index == 0 {
+                offsets.push(-1);
+            }
+            if index == size - 1 {
+                offsets.push(1);
+            }
+            offsets
+        };
+        let [x, y, z] = position;
+        for dy in offsets(h, CHUNK_HEIGHT) {
+            for dx in offsets(w, CHUNK_WIDTH) {
+                for dz in offsets(d, CHUNK_DEPTH) {
+                    dirty.insert([x + dx, y + dy, z + dz]);
+                }
+            }
+        }
+    }
+
+    pub fn neighbors(&self, position: [i64; 3]) -> ChunkNeighbors<'_> {
+        let [x, y, z] = position;
+        ChunkNeighbors {
+            chunks: std::array::from_fn(|h| {
+                std::array::from_fn(|w| {
+                    std::array::from_fn(|d| {
+                        self.get_chunk([x + w as i64 - 1, y + h as i64 - 1, z + d as i64 - 1])
+                    })
+                })
+            }),
+        }
+    }
+}
+
+/// Block a ray ran into, `normal` points out of the face it went in through and is all zeros
+/// when the ray starts inside the block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub block: [i64; 3],
+    pub normal: [i64; 3],
+    /// How far along the ray it went in.
+    pub distance: f32,
+}
+
+/// Which terrain generator a world uses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorldType {
+    #[default]
+    Default,
+    Flat,
+    Debug,
+}
+
+impl FromStr for WorldType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(WorldType::Default),
+            "flat" => Ok(WorldType::Flat),
+            "debug" => Ok(WorldType::Debug),
+            _ => Err(format!("Unknown world type {:?}", s)),
+        }
+    }
+}
+
+/// Everything needed to generate a world again the same way, saved next to it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorldMetadata {
+    pub seed: u32,
+    pub world_type: WorldType,
+    #[serde(default)]
+    pub generator_settings: GeneratorSettings,
+}
+
+impl WorldMetadata {
+    pub fn new(seed: u32, world_type: WorldType) -> WorldMetadata {
+        WorldMetadata {
+            seed,
+            world_type,
+            generator_settings: GeneratorSettings::default(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<WorldMetadata, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read world metadata {}: {}", path, e))?;
+        let metadata: WorldMetadata = toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse world metadata {}: {}", path, e))?;
+        metadata.validate()?;
+        Ok(metadata)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.generator_settings.validate()?;
+        if self.world_type == WorldType::Flat {
+            self.generator_settings.validate_flat()?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize world metadata: {}", e))?;
+        if let Some(dir) = std::path::Path::new(path).parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        std::fs::write(path, contents)
+            .map_err(|e| format!("Failed to write world metadata {}: {}", path, e))
+    }
+
+    /// `blocks` are the blocks the game knows how to draw, laid out by the debug world.
+    pub fn create_generator(&self, blocks: &[BlockId]) -> Box<dyn TerrainGenerator> {
+        match self.world_type {
+            WorldType::Default => Box::new(WorldGenerator::with_settings(
+                self.seed,
+                self.generator_settings.clone(),
+            )),
+            WorldType::Flat => Box::new(FlatGenerator::with_settings(&self.generator_settings)),
+            WorldType::Debug => Box::new(DebugGenerator::new(blocks)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flat_generator::{FlatGenerator, FlatLayer};
+
+    // Stone floor with its top at y = -1, air above
+    fn world() -> World {
+        let generator = FlatGenerator::new(&[FlatLayer::new(BlockId::Stone, 16)]);
+        let mut world = World::new(1);
+        for y in -1..1 {
+            world.generate_chunk([0, y, 0], &generator);
+        }
+        world
+    }
+
+    #[test]
+    fn raycast_hits_floor_from_above() {
+        let world = world();
+        // The top of the floor is half a block above the middle of its blocks
+        let hit = world.raycast([4.0, 2.5, 4.0], [0.0, -1.0, 0.0], 10.0);
+        assert_eq!(
+            hit,
+            Some(RaycastHit {
+                block: [4, -1, 4],
+                normal: [0, 1, 0],
+                distance: 3.0,
+            })
+        );
+    }
+
+    #[test]
+    fn raycast_stops_at_max_distance() {
+        let world = world();
+        assert_eq!(world.raycast([4.0, 2.5, 4.0], [0.0, -1.0, 0.0], 2.0), None);
+    }
+
+    #[test]
+    fn raycast_hits_side_of_block() {
+        let mut world = world();
+        world.set_block([8, 0, 4], BlockId::Glass);
+        let hit = world
+            .raycast([2.0, 0.0, 4.0], [1.0, 0.05, 0.0], 10.0)
+            .unwrap();
+        assert_eq!((hit.block, hit.normal), ([8, 0, 4], [-1, 0, 0]));
+        assert!((hit.distance - 5.5 * 1.0025f32.sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn flat_world_is_made_of_its_layers() {
+        let mut metadata = WorldMetadata::new(0, WorldType::Flat);
+        metadata.generator_settings.flat_layers = vec![
+            FlatLayer::new(BlockId::Sand, 2),
+            FlatLayer::new(BlockId::Glass, 1),
+        ];
+        let saved = toml::to_string_pretty(&metadata).unwrap();
+        let loaded: WorldMetadata = toml::from_str(&saved).unwrap();
+        assert_eq!(loaded, metadata);
+
+        let generator = loaded.create_generator(&[]);
+        let column: Vec<BlockId> = (-4..1).map(|y| generator.get_block_at([3, y, 7])).collect();
+        assert_eq!(
+            column,
+            [
+                BlockId::Air,
+                BlockId::Sand,
+                BlockId::Sand,
+                BlockId::Glass,
+                BlockId::Air
+            ]
+        );
+    }
+
+    #[test]
+    fn flat_world_sits_on_sea_level() {
+        let mut metadata = WorldMetadata::new(0, WorldType::Flat);
+        metadata.generator_settings.sea_level = 64;
+        let generator = metadata.create_generator(&[]);
+        assert_eq!(generator.get_block_at([0, 63, 0]), BlockId::Grass);
+        assert_eq!(generator.get_block_at([0, 64, 0]), BlockId::Air);
+        assert_eq!(generator.get_block_at([0, -64, 0]), BlockId::Stone);
+        assert_eq!(generator.get_block_at([0, -65, 0]), BlockId::Air);
+
+        // Whatever reaches below min_y is cut off
+        metadata.generator_settings.min_y = 0;
+        let generator = metadata.create_generator(&[]);
+        assert_eq!(generator.get_block_at([0, 0, 0]), BlockId::Stone);
+        assert_eq!(generator.get_block_at([0, -1, 0]), BlockId::Air);
+    }
+
+    #[test]
+    fn only_flat_worlds_need_room_for_the_layers() {
+        // Too shallow for the 128 default layers, fine for the noise terrain
+        let mut metadata = WorldMetadata::new(0, WorldType::Default);
+        metadata.generator_settings.min_y = 0;
+        metadata.generator_settings.sea_level = 64;
+        assert_eq!(metadata.validate(), Ok(()));
+
+        metadata.world_type = WorldType::Flat;
+        let error = metadata.validate().unwrap_err();
+        assert!(error.contains("flat_layers"), "{}", error);
+    }
 }
@@ -1,28 +1,198 @@
-use noise::{NoiseFn, OpenSimplex, Perlin, Seedable};
+use noise::NoiseFn;
 
-const ROCK_MAX_HEIGHT: i64 = 100;
-const DIRT_LAYER_HEIGHT: i64 = 120;
-const HILL_LAYER_HEIGHT: i64 = 148;
-const HILL_MAX_HEIGHT: i64 = 32;
+// One in how many grass columns grows a tree / gets a prefab structure.
+const TREE_RARITY: u64 = 48;
+const STRUCTURE_RARITY: u64 = 2048;
 
-const TOTAL_HEIGHT: i64 = ROCK_MAX_HEIGHT + DIRT_LAYER_HEIGHT + HILL_LAYER_HEIGHT + HILL_MAX_HEIGHT;
+// How far (in blocks) a feature may reach out from the column it is rooted in.
+// Chunks look this far past their borders for feature origins.
+const FEATURE_MAX_RADIUS: i64 = 2;
 
-use crate::block::BlockId;
+use crate::{block::BlockId, generator_settings::GeneratorSettings};
+
+/// A small hand-built arrangement of blocks, placed relative to the grass
+/// block it is rooted on (offset `[0, 1, 0]` is the block right above it).
+pub struct Structure {
+    pub name: &'static str,
+    pub blocks: &'static [([i64; 3], BlockId)],
+}
+
+pub const STRUCTURES: &[Structure] = &[
+    Structure {
+        name: "boulder",
+        blocks: &[
+            ([0, 0, 0], BlockId::Stone),
+            ([1, 0, 0], BlockId::Stone),
+            ([0, 0, 1], BlockId::Stone),
+            ([-1, 0, 0], BlockId::Stone),
+            ([0, 1, 0], BlockId::Stone),
+            ([1, 1, 0], BlockId::Stone),
+            ([0, 1, 1], BlockId::Stone),
+            ([0, 2, 0], BlockId::Stone),
+        ],
+    },
+    Structure {
+        name: "ruin",
+        blocks: &[
+            ([-2, 1, -2], BlockId::Stone),
+            ([-2, 2, -2], BlockId::Stone),
+            ([-2, 3, -2], BlockId::Stone),
+            ([2, 1, -2], BlockId::Stone),
+            ([2, 2, -2], BlockId::Stone),
+            ([-2, 1, 2], BlockId::Stone),
+            ([2, 1, 2], BlockId::Stone),
+            ([2, 2, 2], BlockId::Stone),
+            ([2, 3, 2], BlockId::Stone),
+            ([-1, 1, -2], BlockId::Stone),
+            ([1, 1, 2], BlockId::Stone),
+            ([0, 1, 0], BlockId::Lamp),
+        ],
+    },
+];
+
+/// Anything that can fill chunks with blocks.
+///
+/// Implementations must be pure functions of the world coordinates so chunks
+/// come out the same no matter in which order they get generated.
+pub trait TerrainGenerator {
+    fn get_block_at(&self, point: [i64; 3]) -> BlockId;
+
+    /// Blocks placed on top of the base terrain inside the box starting at `min` with size `size`.
+    /// Later entries win over earlier ones.
+    fn features_in(&self, _min: [i64; 3], _size: [i64; 3]) -> Vec<([i64; 3], BlockId)> {
+        Vec::new()
+    }
+}
+
+/// Something that gets stamped onto the terrain after the base layers are generated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Feature {
+    Tree { trunk_height: i64 },
+    Structure(usize),
+}
 
 pub struct WorldGenerator {
+    seed: u32,
+    settings: GeneratorSettings,
     noise_3d: Box<dyn NoiseFn<f64, 3>>,
     noise_2d: Box<dyn NoiseFn<f64, 2>>,
 }
 
 impl WorldGenerator {
     pub fn new(seed: u32) -> Self {
+        Self::with_settings(seed, GeneratorSettings::default())
+    }
+
+    pub fn with_settings(seed: u32, settings: GeneratorSettings) -> Self {
         let noise_3d = Box::new(noise::Perlin::new(seed));
         let noise_2d = Box::new(noise::Perlin::new(seed));
-        Self { noise_3d, noise_2d }
+        Self {
+            seed,
+            settings,
+            noise_3d,
+            noise_2d,
+        }
+    }
+
+    pub fn settings(&self) -> &GeneratorSettings {
+        &self.settings
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// How far up the hill layer a column goes, between 0 and 1.
+    pub fn hill_noise(&self, x: i64, z: i64) -> f64 {
+        let factor = self.settings.hill_noise_scale;
+        let height = self.noise_2d.get([x as f64 / factor, z as f64 / factor]);
+        (height + 1.0) / 2.0
+    }
+
+    fn hill_height(&self, x: i64, z: i64) -> f64 {
+        (self.settings.sea_level + self.settings.dirt_layer_height) as f64
+            + self.hill_noise(x, z) * self.settings.hill_max_height as f64
+    }
+
+    /// Y of the topmost grass block of a column, if the column has grass on top.
+    pub fn surface_height(&self, x: i64, z: i64) -> Option<i64> {
+        let sea_level = self.settings.sea_level;
+        let top = (self.hill_height(x, z).ceil() as i64 - 1)
+            .min(sea_level + self.settings.hill_layer_height - 1);
+        if top >= sea_level + self.settings.dirt_layer_height
+            && self.get_block_at([x, top, z]) == BlockId::Grass
+        {
+            Some(top)
+        } else {
+            None
+        }
+    }
+
+    /// Decides which feature (if any) is rooted at a column.
+    /// Only depends on the seed and the column, never on what has been generated already.
+    pub fn feature_at(&self, x: i64, z: i64) -> Option<Feature> {
+        let hash = column_hash(self.seed, x, z);
+        if hash.is_multiple_of(STRUCTURE_RARITY) {
+            Some(Feature::Structure(
+                ((hash / STRUCTURE_RARITY) % STRUCTURES.len() as u64) as usize,
+            ))
+        } else if hash % TREE_RARITY == 1 {
+            Some(Feature::Tree {
+                trunk_height: 4 + ((hash / TREE_RARITY) % 3) as i64,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn feature_blocks(
+        &self,
+        feature: Feature,
+        root: [i64; 3],
+        mut place: impl FnMut([i64; 3], BlockId),
+    ) {
+        match feature {
+            Feature::Tree { trunk_height } => {
+                let top = root[1] + trunk_height;
+                for y in top - 2..=top + 1 {
+                    let radius: i64 = if y > top - 1 { 1 } else { 2 };
+                    for dx in -radius..=radius {
+                        for dz in -radius..=radius {
+                            // Trim the corners so the canopy looks round-ish
+                            if radius == 2 && dx.abs() == 2 && dz.abs() == 2 {
+                                continue;
+                            }
+                            place([root[0] + dx, y, root[2] + dz], BlockId::Leaves);
+                        }
+                    }
+                }
+                for y in root[1] + 1..=top {
+                    place([root[0], y, root[2]], BlockId::Log);
+                }
+            }
+            Feature::Structure(index) => {
+                for (offset, block) in STRUCTURES[index].blocks {
+                    place(
+                        [
+                            root[0] + offset[0],
+                            root[1] + offset[1],
+                            root[2] + offset[2],
+                        ],
+                        *block,
+                    );
+                }
+            }
+        }
     }
-    pub fn get_block_at(&self, point: [i64; 3]) -> BlockId {
-        let factor = 5.0;
-        if point[1] < ROCK_MAX_HEIGHT {
+}
+
+impl TerrainGenerator for WorldGenerator {
+    fn get_block_at(&self, point: [i64; 3]) -> BlockId {
+        let factor = self.settings.cave_noise_scale;
+        let height = point[1] - self.settings.sea_level;
+        if point[1] < self.settings.min_y || point[1] >= self.settings.max_y {
+            BlockId::Air
+        } else if height < self.settings.rock_max_height {
             let noise = self.noise_3d.get([
                 point[0] as f64 / factor,
                 point[1] as f64 / factor,
@@ -37,17 +207,10 @@ impl WorldGenerator {
             } else {
                 BlockId::Air
             }
-        } else if point[1] < DIRT_LAYER_HEIGHT {
+        } else if height < self.settings.dirt_layer_height {
             BlockId::Dirt
-        } else if point[1] < HILL_LAYER_HEIGHT {
-            let factor = 30.0;
-            let height = self
-                .noise_2d
-                .get([point[0] as f64 / factor, point[2] as f64 / factor]);
-
-            if (point[1] as f64)
-                < DIRT_LAYER_HEIGHT as f64 + ((height + 1.0) / 2.0) * HILL_MAX_HEIGHT as f64
-            {
+        } else if height < self.settings.hill_layer_height {
+            if (point[1] as f64) < self.hill_height(point[0], point[2]) {
                 BlockId::Grass
             } else {
                 BlockId::Air
@@ -56,4 +219,116 @@ impl WorldGenerator {
             BlockId::Air
         }
     }
+
+    /// Features can hang over chunk borders, so instead of remembering what a neighbor
+    /// still owes us, every chunk scans the columns around it for feature origins and keeps
+    /// the part that falls inside it. The result is the same whichever chunk generates first.
+    /// Blocks are returned in a fixed order, later entries win over earlier ones.
+    fn features_in(&self, min: [i64; 3], size: [i64; 3]) -> Vec<([i64; 3], BlockId)> {
+        let mut blocks = Vec::new();
+        let max = [min[0] + size[0], min[1] + size[1], min[2] + size[2]];
+
+        for x in min[0] - FEATURE_MAX_RADIUS..max[0] + FEATURE_MAX_RADIUS {
+            for z in min[2] - FEATURE_MAX_RADIUS..max[2] + FEATURE_MAX_RADIUS {
+                let Some(feature) = self.feature_at(x, z) else {
+                    continue;
+                };
+                let Some(y) = self.surface_height(x, z) else {
+                    continue;
+                };
+
+                self.feature_blocks(feature, [x, y, z], |point, block| {
+                    if (0..3).all(|i| point[i] >= min[i] && point[i] < max[i]) {
+                        blocks.push((point, block));
+                    }
+                });
+            }
+        }
+
+        blocks
+    }
+}
+
+// SplitMix64 over the seed and column, good enough to scatter features evenly.
+fn column_hash(seed: u32, x: i64, z: i64) -> u64 {
+    let mut h = (seed as u64)
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (z as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^ (h >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::{CHUNK_DEPTH, CHUNK_WIDTH},
+        world::World,
+    };
+
+    /// Root and trunk height of a tree `distance` blocks short of the border at x = CHUNK_WIDTH.
+    fn tree_near_border(generator: &WorldGenerator, distance: i64) -> ([i64; 3], i64) {
+        let x = CHUNK_WIDTH as i64 - distance;
+        (0..4096)
+            .find_map(|z| match generator.feature_at(x, z)? {
+                Feature::Tree { trunk_height } => {
+                    Some(([x, generator.surface_height(x, z)?, z], trunk_height))
+                }
+                Feature::Structure(_) => None,
+            })
+            .expect("No tree along the border")
+    }
+
+    // Chunk columns from the ground up to above the highest trees, in the given order
+    fn generate(generator: &WorldGenerator, columns: &[[i64; 2]]) -> World {
+        let mut world = World::new(1);
+        for [x, z] in columns {
+            for y in 0..4 {
+                world.generate_chunk([*x, y, *z], generator);
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn tree_over_border_is_the_same_in_any_order() {
+        let generator = WorldGenerator::new(1234);
+        let (root, trunk_height) = tree_near_border(&generator, 1);
+        let z = root[2].div_euclid(CHUNK_DEPTH as i64);
+        let a_first = generate(&generator, &[[0, z], [1, z]]);
+        let b_first = generate(&generator, &[[1, z], [0, z]]);
+
+        let leaf = [CHUNK_WIDTH as i64, root[1] + trunk_height - 1, root[2]];
+        assert_eq!(a_first.get_block(leaf), Some(BlockId::Leaves));
+        for x in 0..2 * CHUNK_WIDTH as i64 {
+            for y in 0..64 {
+                for z in z * CHUNK_DEPTH as i64..(z + 1) * CHUNK_DEPTH as i64 {
+                    assert_eq!(
+                        a_first.get_block([x, y, z]),
+                        b_first.get_block([x, y, z]),
+                        "{:?}",
+                        [x, y, z]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn leaves_reach_neighbor_from_furthest_trunk() {
+        let generator = WorldGenerator::new(1234);
+        let (root, trunk_height) = tree_near_border(&generator, FEATURE_MAX_RADIUS);
+        // Only the neighbor is generated, the chunk with the trunk never is
+        let z = root[2].div_euclid(CHUNK_DEPTH as i64);
+        let world = generate(&generator, &[[1, z]]);
+
+        let leaf = [CHUNK_WIDTH as i64, root[1] + trunk_height - 1, root[2]];
+        assert_eq!(world.get_block(leaf), Some(BlockId::Leaves));
+        assert_eq!(
+            world.get_block([root[0], root[1] + 1, root[2]]),
+            None,
+            "trunk is outside the generated chunks"
+        );
+    }
 }
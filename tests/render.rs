@@ -0,0 +1,162 @@
+//! Renders scenes offscreen and compares them against the golden images in `tests/golden`.
+//! Runs on software rendering, no window or GPU needed, just Mesa's surfaceless EGL.
+//!
+//! A missing golden image is written by the test. Set `UPDATE_GOLDEN=1` to rewrite them all
+//! after a change that is meant to look different.
+//!
+//! Without EGL the tests fail, so a broken setup can't pass for a clean run. Set
+//! `SKIP_RENDER_TESTS=1` on machines that can't render to skip them instead.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use image::RgbaImage;
+use mc_rs::{
+    camera::PerspectiveCamera3D,
+    headless,
+    renderer::{Renderer, ATLAS_BLOCKS},
+    sky::{WorldTime, DAY_LENGTH},
+    transform::Transform,
+    vector3::Vector3,
+    world::{World, WorldMetadata, WorldType},
+};
+
+const SIZE: (u32, u32) = (160, 120);
+
+/// Channels can be this far off before a pixel counts as different, drivers round differently.
+const CHANNEL_TOLERANCE: u8 = 8;
+/// Share of the pixels that may differ, edges of triangles move around between drivers.
+const PIXEL_TOLERANCE: f64 = 0.01;
+
+struct Scene {
+    name: &'static str,
+    seed: u32,
+    world_type: WorldType,
+    position: [f32; 3],
+    /// Pitch and yaw in degrees.
+    rotation: [f32; 2],
+    ticks: u64,
+}
+
+fn render(scene: &Scene) -> Option<RgbaImage> {
+    let context = match headless::create_context(SIZE) {
+        Ok(context) => context,
+        Err(e) if std::env::var("SKIP_RENDER_TESTS").is_ok_and(|v| v == "1") => {
+            // Straight to stderr, the test harness swallows `eprintln!` of passing tests
+            let _ = writeln!(
+                std::io::stderr(),
+                "SKIPPED {}, no headless OpenGL: {}",
+                scene.name,
+                e
+            );
+            return None;
+        }
+        Err(e) => panic!(
+            "No headless OpenGL for {}, set SKIP_RENDER_TESTS=1 to skip: {}",
+            scene.name, e
+        ),
+    };
+
+    let metadata = WorldMetadata::new(scene.seed, scene.world_type);
+    let generator = metadata.create_generator(&ATLAS_BLOCKS);
+    let mut world = World::new(1);
+    for x in -1..=1 {
+        for y in -1..=2 {
+            for z in -1..=1 {
+                world.generate_chunk([x, y, z], generator.as_ref());
+            }
+        }
+    }
+    world.compute_light();
+
+    let mut renderer = Renderer::new(&context, 48.0).expect("Failed to create renderer");
+    renderer.mesh_world(&context, &world);
+
+    let camera = PerspectiveCamera3D::new(
+        SIZE.0 as f32 / SIZE.1 as f32,
+        70f32.to_radians(),
+        0.01,
+        1024.0,
+    );
+    let mut transform = Transform::zero();
+    let [x, y, z] = scene.position;
+    transform.position = Vector3 { x, y, z };
+    let [pitch, yaw] = scene.rotation;
+    transform.set_yaw_pitch(yaw.to_radians(), pitch.to_radians());
+
+    let time = WorldTime { ticks: scene.ticks };
+    Some(renderer.render_to_image(&context, SIZE, &camera, transform, &time))
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new("tests/golden").join(format!("{}.png", name))
+}
+
+/// Share of pixels with any channel more than `CHANNEL_TOLERANCE` off.
+fn difference(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    let different = a
+        .pixels()
+        .zip(b.pixels())
+        .filter(|(a, b)| {
+            a.0.iter()
+                .zip(b.0.iter())
+                .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+        })
+        .count();
+    different as f64 / (a.width() * a.height()) as f64
+}
+
+fn check(scene: Scene) {
+    let Some(image) = render(&scene) else {
+        return;
+    };
+    let path = golden_path(scene.name);
+    if std::env::var("UPDATE_GOLDEN").is_ok_and(|v| v == "1") || !path.exists() {
+        image.save(&path).expect("Failed to write golden image");
+        println!("Wrote {}", path.display());
+        return;
+    }
+
+    let golden = image::open(&path)
+        .expect("Failed to read golden image")
+        .to_rgba8();
+    assert_eq!(golden.dimensions(), image.dimensions(), "{}", scene.name);
+    let difference = difference(&golden, &image);
+    if difference > PIXEL_TOLERANCE {
+        let actual = std::env::temp_dir().join(format!("{}.actual.png", scene.name));
+        image.save(&actual).expect("Failed to write rendered image");
+        panic!(
+            "{} differs from {} in {:.1}% of the pixels, got {}",
+            scene.name,
+            path.display(),
+            difference * 100.0,
+            actual.display()
+        );
+    }
+}
+
+#[test]
+fn hills_at_noon() {
+    check(Scene {
+        name: "hills_at_noon",
+        seed: 1234,
+        world_type: WorldType::Default,
+        position: [8.0, 40.0, 40.0],
+        rotation: [-30.0, 0.0],
+        ticks: DAY_LENGTH / 4,
+    });
+}
+
+#[test]
+fn debug_world_at_sunset() {
+    check(Scene {
+        name: "debug_world_at_sunset",
+        seed: 0,
+        world_type: WorldType::Debug,
+        position: [-4.0, 6.0, -4.0],
+        rotation: [-25.0, -135.0],
+        ticks: DAY_LENGTH / 2 - 200,
+    });
+}
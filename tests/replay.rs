@@ -0,0 +1,141 @@
+//! Plays recorded sessions again and checks that the player goes through the same places and
+//! edits the same blocks. Nothing is drawn, no window or OpenGL needed.
+//!
+//! The recordings in `tests/replays` are made from the scripts below when they are missing. Set
+//! `UPDATE_GOLDEN=1` to record them all again after a change that is meant to play differently.
+
+use std::path::{Path, PathBuf};
+
+use glium::glutin::event::{MouseButton, VirtualKeyCode};
+use mc_rs::{
+    actions::Bindings,
+    block::BlockId,
+    replay::{Recorder, Recording},
+    settings::Settings,
+    simulation::{InputEvent, Simulation},
+    world::{WorldMetadata, WorldType},
+};
+
+struct Script {
+    name: &'static str,
+    seed: u32,
+    world_type: WorldType,
+    ticks: u64,
+    /// Events and the tick they come in before, in order.
+    events: &'static [(u64, InputEvent)],
+}
+
+const fn key(key: VirtualKeyCode, pressed: bool) -> InputEvent {
+    InputEvent::Key { key, pressed }
+}
+
+const fn button(button: MouseButton, pressed: bool) -> InputEvent {
+    InputEvent::MouseButton { button, pressed }
+}
+
+const fn look(dx: f32, dy: f32) -> InputEvent {
+    InputEvent::MouseMotion { dx, dy }
+}
+
+const WALK_AND_LOOK: Script = Script {
+    name: "walk_and_look",
+    seed: 1234,
+    world_type: WorldType::Default,
+    ticks: 300,
+    events: &[
+        (0, key(VirtualKeyCode::W, true)),
+        (30, look(120.0, 0.0)),
+        (31, look(80.0, -40.0)),
+        (90, key(VirtualKeyCode::D, true)),
+        (150, key(VirtualKeyCode::W, false)),
+        (160, look(-300.0, 25.0)),
+        (200, key(VirtualKeyCode::D, false)),
+        (210, key(VirtualKeyCode::Space, true)),
+        (240, key(VirtualKeyCode::Space, false)),
+    ],
+};
+
+// Flies down onto the flat world, digs out the block underneath and fills the hole with glass
+const DIG_AND_BUILD: Script = Script {
+    name: "dig_and_build",
+    seed: 1,
+    world_type: WorldType::Flat,
+    ticks: 420,
+    events: &[
+        (0, look(0.0, 500.0)),
+        (0, key(VirtualKeyCode::C, true)),
+        (0, key(VirtualKeyCode::D, true)),
+        (40, key(VirtualKeyCode::D, false)),
+        (270, key(VirtualKeyCode::C, false)),
+        (340, button(MouseButton::Left, true)),
+        (345, button(MouseButton::Left, false)),
+        (350, key(VirtualKeyCode::Key7, true)),
+        (352, key(VirtualKeyCode::Key7, false)),
+        (360, button(MouseButton::Right, true)),
+        (361, button(MouseButton::Right, false)),
+    ],
+};
+
+fn record(script: &Script) -> Recording {
+    let metadata = WorldMetadata::new(script.seed, script.world_type);
+    let settings = Settings::default();
+    let bindings = Bindings::default();
+    let mut recorder = Recorder::new(&metadata, &settings, &bindings);
+    let mut simulation = Simulation::new(&metadata, &settings, bindings);
+
+    let mut events = script.events.iter().peekable();
+    while simulation.ticks < script.ticks {
+        while let Some((_, event)) = events.next_if(|(tick, _)| *tick <= simulation.ticks) {
+            recorder.record_event(&simulation, *event);
+            simulation.process_event(event);
+        }
+        simulation.tick();
+        recorder.record_tick(&simulation);
+    }
+    recorder.finish(&simulation)
+}
+
+fn recording_path(name: &str) -> PathBuf {
+    Path::new("tests/replays").join(format!("{}.toml", name))
+}
+
+fn load_or_record(script: &Script) -> Recording {
+    let path = recording_path(script.name);
+    let path = path.to_str().unwrap();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() || !Path::new(path).exists() {
+        record(script)
+            .save(path)
+            .unwrap_or_else(|e| panic!("{}", e));
+        eprintln!("Recorded {}", path);
+    }
+    Recording::load(path).unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[test]
+fn walk_and_look() {
+    let recording = load_or_record(&WALK_AND_LOOK);
+    recording
+        .verify()
+        .unwrap_or_else(|e| panic!("{}: {}", WALK_AND_LOOK.name, e));
+}
+
+#[test]
+fn dig_and_build() {
+    let recording = load_or_record(&DIG_AND_BUILD);
+    recording
+        .verify()
+        .unwrap_or_else(|e| panic!("{}: {}", DIG_AND_BUILD.name, e));
+
+    let blocks: Vec<BlockId> = recording.edits.iter().map(|edit| edit.block).collect();
+    assert_eq!(blocks, vec![BlockId::Air, BlockId::Glass]);
+    assert_eq!(recording.edits[0].position, recording.edits[1].position);
+}
+
+#[test]
+fn replay_notices_missing_input() {
+    let mut recording = load_or_record(&WALK_AND_LOOK);
+    recording
+        .events
+        .retain(|recorded| !matches!(recorded.event, InputEvent::MouseMotion { .. }));
+    assert!(recording.verify().is_err());
+}